@@ -0,0 +1,21 @@
+use geospatial::{edges_to_multilinestring, marching_squares};
+use ndarray::Array2;
+use std::time::Instant;
+
+// the worst case for ring assembly, every label is thousands of one cell rings
+fn main() {
+    let n = 500;
+    let grid = Array2::from_shape_fn((n, n), |(r, c)| (r + c) % 2);
+
+    let e = marching_squares(&grid);
+    let t = Instant::now();
+    let rings: usize = e
+        .iter()
+        .map(|(id, edges)| edges_to_multilinestring(*id, edges, &grid).0.len())
+        .sum();
+    println!(
+        "edges_to_multilinestring: {:?} for {} rings",
+        t.elapsed(),
+        rings
+    );
+}
@@ -0,0 +1,29 @@
+use geospatial::{edges_to_multilinestring, marching_squares};
+use ndarray::Array2;
+use std::time::Instant;
+
+// run with and without --features fxhash to compare the hashers
+fn main() {
+    // 10x10 blocks, so 250000 labels
+    let n = 5000;
+    let grid = Array2::from_shape_fn((n, n), |(r, c)| (r / 10) * n + c / 10);
+
+    let t = Instant::now();
+    let e = marching_squares(&grid);
+    println!(
+        "marching_squares: {:?} for {} regions",
+        t.elapsed(),
+        e.len()
+    );
+
+    let t = Instant::now();
+    let rings: usize = e
+        .iter()
+        .map(|(id, edges)| edges_to_multilinestring(*id, edges, &grid).0.len())
+        .sum();
+    println!(
+        "edges_to_multilinestring: {:?} for {} rings",
+        t.elapsed(),
+        rings
+    );
+}
@@ -0,0 +1,17 @@
+use geospatial::{marching_squares, marching_squares_par};
+use ndarray::Array2;
+use std::time::Instant;
+
+fn main() {
+    // lots of little regions so there are plenty of edges
+    let n = 4000;
+    let grid = Array2::from_shape_fn((n, n), |(r, c)| ((r / 7) * 31 + (c / 5) * 17) % 101);
+
+    let t = Instant::now();
+    let e = marching_squares(&grid);
+    println!("serial:   {:?} for {} regions", t.elapsed(), e.len());
+
+    let t = Instant::now();
+    let e = marching_squares_par(&grid);
+    println!("parallel: {:?} for {} regions", t.elapsed(), e.len());
+}
@@ -3,13 +3,87 @@
 //! `geospatial` provides functions for geospatial manipulation that I could not
 //! find in any other rust crate.
 //!
+//! # Coordinate conventions
+//!
+//! Everything works in grid coordinates with `x` the column and `y` the row, but there are
+//! two kinds of point:
+//!
+//! - **Cell indices** (centers).  [`rasterize_linestring`] and friends take and return
+//!   cells, where `Coord { x: c, y: r }` is the cell `grid[[r, c]]`, and the line's
+//!   vertices are thought of at the centers of their cells.
+//! - **Cell corners** (the lattice).  [`marching_squares`], the edge assembly and
+//!   [`trace_regions`] put boundaries between cells, so `Coord { x: c, y: r }` is the top
+//!   left corner of cell `grid[[r, c]]` and its center is at `(c + 0.5, r + 0.5)`.
+//!
+//! Mixing the two needs a half cell shift, [`centers_to_corners`] and
+//! [`corners_to_centers`] do it with the sign the right way round.
+//!
+//! `ndarray` indexes the other way round, `grid[[row, col]]`, which is `[y, x]`.  Getting
+//! this backwards transposes the output, and on a square grid nothing looks wrong.
+//! [`coord_to_index`] and [`index_to_coord`] do the swap.
+//!
 
-use geo::{Coord, CoordNum, LineString, MultiLineString};
-use line_drawing::{SignedNum, Supercover};
-use ndarray::Array2;
-use std::collections::{HashMap, HashSet};
+use geo::{Coord, CoordNum, LineString, MapCoords, MultiLineString, MultiPolygon};
+use line_drawing::{Bresenham, SignedNum, Supercover};
+use ndarray::{Array2, ArrayBase, ArrayView2, Ix2, RawData};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::hash::Hash;
 
+/// An edge on the grid lattice, running between two cell corners.
+pub type GridEdge = (Coord<usize>, Coord<usize>);
+
+/// The map type returned by [`marching_squares`] and its variants.
+///
+/// This is a plain `HashMap`, unless the `fxhash` feature is on, when it uses the much
+/// faster (but not DoS resistant) Fx hasher.  Grids with lots of labels spend most of their
+/// time hashing.
+#[cfg(not(feature = "fxhash"))]
+pub type LabelMap<K, V> = HashMap<K, V>;
+
+/// The map type returned by [`marching_squares`] and its variants.
+///
+/// This is a plain `HashMap`, unless the `fxhash` feature is on, when it uses the much
+/// faster (but not DoS resistant) Fx hasher.  Grids with lots of labels spend most of their
+/// time hashing.
+#[cfg(feature = "fxhash")]
+pub type LabelMap<K, V> = HashMap<K, V, rustc_hash::FxBuildHasher>;
+
+// the set counterpart of LabelMap, used internally
+#[cfg(not(feature = "fxhash"))]
+type LabelSet<K> = HashSet<K>;
+#[cfg(feature = "fxhash")]
+type LabelSet<K> = HashSet<K, rustc_hash::FxBuildHasher>;
+
+/// The `ndarray` index `[row, col]` of the cell at `Coord { x: col, y: row }`
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::Array2;
+///
+/// // 2 rows, 3 columns
+/// let grid = Array2::from_shape_fn((2, 3), |(r, c)| 10 * r + c);
+/// let c = Coord { x: 2, y: 1 };
+/// assert_eq!(geospatial::coord_to_index(c), [1, 2]);
+/// assert_eq!(grid[geospatial::coord_to_index(c)], 12);
+/// for ((r, col), _) in grid.indexed_iter() {
+///     let c = geospatial::index_to_coord([r, col]);
+///     assert_eq!((c.x, c.y), (col, r));
+///     assert_eq!(geospatial::coord_to_index(c), [r, col]);
+/// }
+/// ```
+pub fn coord_to_index(c: Coord<usize>) -> [usize; 2] {
+    [c.y, c.x]
+}
+
+/// The `Coord { x: col, y: row }` of the cell at `ndarray` index `[row, col]`
+///
+/// The inverse of [`coord_to_index`].
+pub fn index_to_coord(ix: [usize; 2]) -> Coord<usize> {
+    Coord { x: ix[1], y: ix[0] }
+}
+
 /// Rasterizes a geo::LineString onto a grid of integer coordinates.
 ///
 /// This function returns a `Vec<Coord<T>>` containing all grid cells that the line
@@ -19,11 +93,19 @@ use std::hash::Hash;
 /// # Parameters
 ///
 /// - `ls`: A reference to a `LineString<T>` to rasterize. Can be empty.  T must be SignedNum eg
-///             isize, i32
+///   isize, i32
 ///
 /// # Returns
 ///
 /// A `Vec<Coord<T>>` representing all the integer grid coordinates traversed by the line.
+/// A line of a single point gives just that point's cell, and an empty line nothing.
+///
+/// # Notes
+///
+/// - The difference between consecutive vertices must fit in `T`, and the traversal steps
+///   one cell past the end of each segment, so vertices must also be at least one away from
+///   the limits of `T`.  Outside this range small types like `i8` or `i16` silently give the
+///   wrong cells, use [`try_rasterize_linestring`] to get an error instead.
 ///
 /// # Examples
 /// ```
@@ -64,462 +146,8536 @@ use std::hash::Hash;
 /// );
 /// let ls: LineString<i32> = LineString::new(vec![]);
 /// assert_eq!(geospatial::rasterize_linestring(&ls), vec![]);
+/// let ls: LineString<i32> = LineString::new(vec![Coord { x: 4, y: -2 }]);
+/// assert_eq!(geospatial::rasterize_linestring(&ls), vec![Coord { x: 4, y: -2 }]);
 /// ```
 pub fn rasterize_linestring<T>(ls: &LineString<T>) -> Vec<Coord<T>>
 where
     T: CoordNum + SignedNum,
 {
-    let mut out = Vec::new();
-    for w in ls.0.windows(2) {
-        for (x, y) in Supercover::new((w[0].x, w[0].y), (w[1].x, w[1].y)) {
-            let c = Coord { x, y };
-            if Some(&c) != out.last() {
-                out.push(c);
-            }
-        }
-    }
-    out
+    rasterize_linestring_iter(ls).collect()
 }
 
-/// Marching squares
-///
-/// Extracts boundary edges from a 2d array.  A horizontal or vertical edge exists between
-/// two cells if they have
-/// different values. It is intended for
-/// grids containing region or watershed labels, where each distinct value represents
-/// a separate area and you want to get the boundary edges.
+// the segments of a line, with a line of one point being a segment of no length so its
+// cell isn't lost
+fn line_segments<T: CoordNum>(
+    ls: &LineString<T>,
+) -> impl Iterator<Item = (Coord<T>, Coord<T>)> + '_ {
+    let single = (ls.0.len() == 1).then(|| (ls.0[0], ls.0[0]));
+    ls.0.windows(2).map(|w| (w[0], w[1])).chain(single)
+}
+
+/// [`rasterize_linestring`] as a lazy iterator
 ///
-/// # Parameters
+/// Yields the same cells in the same order, without building a `Vec`, for burning very
+/// long lines straight into a grid.
 ///
-/// - `grid`: A 2D array of values representing labeled regions.
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+/// use ndarray::Array2;
 ///
-/// # Returns
+/// let ls: LineString<isize> = LineString::from(vec![(0, 0), (3, 0), (3, 2)]);
+/// let mut grid = Array2::<u8>::zeros((3, 4));
+/// for c in geospatial::rasterize_linestring_iter(&ls) {
+///     grid[[c.y as usize, c.x as usize]] += 1;
+/// }
+/// // the corner is only yielded once
+/// assert_eq!(grid.sum(), 6);
+/// assert_eq!(grid[[0, 3]], 1);
+/// ```
+pub fn rasterize_linestring_iter<'a, T>(
+    ls: &'a LineString<T>,
+) -> impl Iterator<Item = Coord<T>> + 'a
+where
+    T: CoordNum + SignedNum + 'a,
+{
+    let mut last: Option<Coord<T>> = None;
+    line_segments(ls)
+        .flat_map(|(a, b)| Supercover::new((a.x, a.y), (b.x, b.y)))
+        .map(|(x, y)| Coord { x, y })
+        .filter(move |c| {
+            let fresh = last != Some(*c);
+            last = Some(*c);
+            fresh
+        })
+}
+
+/// Rasterize a `LineString` as a thin line, one cell per step along the longer axis
 ///
-/// A HashMap mapping each unique grid value to a list of edges `(Coord<usize>, Coord<usize>)`
-/// associated with that region.
+/// [`rasterize_linestring`] takes every cell the line passes through, so a shallow
+/// diagonal comes out two cells thick in places.  This uses Bresenham's algorithm
+/// instead, which steps diagonally through corners and gives a crisp single pixel line.
+/// Consecutive repeated cells, where segments join, are removed just the same.
 ///
 /// # Notes
 ///
-/// - Interior cells that are completely surrounded by the same value won't generate an edge.
-/// - This function does **not** return full polygon boundaries; it only identifies
-///   boundary edges that will need to be assembled into a polygon
+/// - Neighbouring cells of the result can touch only at a corner, so a region bounded by
+///   such a line can leak through it under 4-connectivity.
 ///
 /// # Examples
-///
 /// ```
-/// use ndarray::{array, Array2};
-/// use std::collections::HashMap;
-/// use geo::{Coord};
-/// use std::hash::Hash;
-///
-/// let grid = array![
-///     [1],
-/// ];
-/// assert_eq!(geospatial::marching_squares(&grid)[&1],
-///     vec![
-///         (Coord{ x: 0, y: 0}, Coord{ x: 1, y: 0}),
-///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
-///         (Coord{ x: 0, y: 0}, Coord{ x: 0, y: 1}),
-///         (Coord{ x: 1, y: 0}, Coord{ x: 1, y: 1}),
-///     ]
-/// );
-///
-/// let grid = array![
-///     [1, 1],
-/// ];
-/// assert_eq!(geospatial::marching_squares(&grid)[&1],
-///     vec![
-///         (Coord{ x: 0, y: 0}, Coord{ x: 1, y: 0}),
-///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
-///         (Coord{ x: 1, y: 0}, Coord{ x: 2, y: 0}),
-///         (Coord{ x: 1, y: 1}, Coord{ x: 2, y: 1}),
-///         (Coord{ x: 0, y: 0}, Coord{ x: 0, y: 1}),
-///         (Coord{ x: 2, y: 0}, Coord{ x: 2, y: 1}),
-///     ]
-/// );
+/// use geo::{Coord, LineString};
 ///
-/// let grid = array![
-///     [1, 1],
-///     [2, 1],
-/// ];
-/// let e = geospatial::marching_squares(&grid);
-/// assert_eq!(e[&1],
-///     vec![
-///         (Coord{ x: 0, y: 0}, Coord{ x: 1, y: 0}),
-///         (Coord{ x: 1, y: 0}, Coord{ x: 2, y: 0}),
-///         (Coord{ x: 1, y: 2}, Coord{ x: 2, y: 2}),
-///         (Coord{ x: 0, y: 0}, Coord{ x: 0, y: 1}),
-///         (Coord{ x: 2, y: 0}, Coord{ x: 2, y: 1}),
-///         (Coord{ x: 2, y: 1}, Coord{ x: 2, y: 2}),
-///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
-///         (Coord{ x: 1, y: 1}, Coord{ x: 1, y: 2}),
-///     ]
-/// );
-/// assert_eq!(e[&2],
-///     vec![
-///         (Coord{ x: 0, y: 2}, Coord{ x: 1, y: 2}),
-///         (Coord{ x: 0, y: 1}, Coord{ x: 0, y: 2}),
-///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
-///         (Coord{ x: 1, y: 1}, Coord{ x: 1, y: 2}),
-///     ]
-/// );
-/// let grid = array![
-///     [4, 1, 1, 2],
-///     [1, 1, 2, 3],
-///     [1, 2, 2, 2],
-/// ];
-/// let e = geospatial::marching_squares(&grid);
-/// assert_eq!(e[&4],
-///     vec![
-///         (Coord{ x: 0, y: 0}, Coord{ x: 1, y: 0}),
-///         (Coord{ x: 0, y: 0}, Coord{ x: 0, y: 1}),
-///         (Coord{ x: 1, y: 0}, Coord{ x: 1, y: 1}),
-///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
-///     ]
-/// );
-/// assert_eq!(e[&3],
-///     vec![
-///         (Coord{ x: 4, y: 1}, Coord{ x: 4, y: 2}),
-///         (Coord{ x: 3, y: 1}, Coord{ x: 3, y: 2}),
-///         (Coord{ x: 3, y: 1}, Coord{ x: 4, y: 1}),
-///         (Coord{ x: 3, y: 2}, Coord{ x: 4, y: 2}),
-///     ]
-/// );
-/// assert_eq!(e[&2],
+/// let ls: LineString<i32> = LineString::from(vec![(0, 0), (4, 2)]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_bresenham(&ls),
 ///     vec![
-///         (Coord{ x: 1, y: 3}, Coord{ x: 2, y: 3}),
-///         (Coord{ x: 2, y: 3}, Coord{ x: 3, y: 3}),
-///         (Coord{ x: 3, y: 0}, Coord{ x: 4, y: 0}),
-///         (Coord{ x: 3, y: 3}, Coord{ x: 4, y: 3}),
-///         (Coord{ x: 4, y: 0}, Coord{ x: 4, y: 1}),
-///         (Coord{ x: 4, y: 2}, Coord{ x: 4, y: 3}),
-///         (Coord{ x: 3, y: 0}, Coord{ x: 3, y: 1}),
-///         (Coord{ x: 2, y: 1}, Coord{ x: 3, y: 1}),
-///         (Coord{ x: 2, y: 1}, Coord{ x: 2, y: 2}),
-///         (Coord{ x: 1, y: 2}, Coord{ x: 2, y: 2}),
-///         (Coord{ x: 3, y: 1}, Coord{ x: 3, y: 2}),
-///         (Coord{ x: 3, y: 1}, Coord{ x: 4, y: 1}),
-///         (Coord{ x: 3, y: 2}, Coord{ x: 4, y: 2}),
-///         (Coord{ x: 1, y: 2}, Coord{ x: 1, y: 3}),
+///         Coord { x: 0, y: 0 },
+///         Coord { x: 1, y: 0 },
+///         Coord { x: 2, y: 1 },
+///         Coord { x: 3, y: 1 },
+///         Coord { x: 4, y: 2 },
 ///     ]
 /// );
-/// assert_eq!(e[&1],
+/// // the supercover also takes the cells the line only clips
+/// assert_eq!(
+///     geospatial::rasterize_linestring(&ls),
 ///     vec![
-///         (Coord{ x: 0, y: 3}, Coord{ x: 1, y: 3}),
-///         (Coord{ x: 1, y: 0}, Coord{ x: 2, y: 0}),
-///         (Coord{ x: 2, y: 0}, Coord{ x: 3, y: 0}),
-///         (Coord{ x: 0, y: 1}, Coord{ x: 0, y: 2}),
-///         (Coord{ x: 0, y: 2}, Coord{ x: 0, y: 3}),
-///         (Coord{ x: 1, y: 0}, Coord{ x: 1, y: 1}),
-///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
-///         (Coord{ x: 3, y: 0}, Coord{ x: 3, y: 1}),
-///         (Coord{ x: 2, y: 1}, Coord{ x: 3, y: 1}),
-///         (Coord{ x: 2, y: 1}, Coord{ x: 2, y: 2}),
-///         (Coord{ x: 1, y: 2}, Coord{ x: 2, y: 2}),
-///         (Coord{ x: 1, y: 2}, Coord{ x: 1, y: 3}),
+///         Coord { x: 0, y: 0 },
+///         Coord { x: 1, y: 0 },
+///         Coord { x: 1, y: 1 },
+///         Coord { x: 2, y: 1 },
+///         Coord { x: 3, y: 1 },
+///         Coord { x: 3, y: 2 },
+///         Coord { x: 4, y: 2 },
 ///     ]
 /// );
-///
 /// ```
-pub fn marching_squares<T>(grid: &Array2<T>) -> HashMap<T, Vec<(Coord<usize>, Coord<usize>)>>
+pub fn rasterize_linestring_bresenham<T>(ls: &LineString<T>) -> Vec<Coord<T>>
 where
-    T: Eq + Hash + Copy,
+    T: CoordNum + SignedNum,
 {
-    let mut ret: HashMap<T, Vec<(Coord<usize>, Coord<usize>)>> = HashMap::new();
-    let (nrows, ncols) = grid.dim();
-
-    // we need edges around the entire grid, process top/bot row and left/right col at same time
-    for c in 0..ncols {
-        let r = 0;
-        let me = grid[[r, c]];
-        let edge = (Coord { x: c, y: r }, Coord { x: c + 1, y: r });
-        ret.entry(me).or_default().push(edge);
-        let r = nrows - 1;
-        let me = grid[[r, c]];
-        let edge = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
-        ret.entry(me).or_default().push(edge);
-    }
-    for r in 0..nrows {
-        let c = 0;
-        let me = grid[[r, c]];
-        let edge = (Coord { x: c, y: r }, Coord { x: c, y: r + 1 });
-        ret.entry(me).or_default().push(edge);
-        let c = ncols - 1;
-        let me = grid[[r, c]];
-        let edge = (Coord { x: c + 1, y: r }, Coord { x: c + 1, y: r + 1 });
-        ret.entry(me).or_default().push(edge);
-    }
-
-    // fill in the interior
-    for r in 0..nrows - 1 {
-        for c in 0..ncols - 1 {
-            let me = grid[[r, c]];
-            let right = grid[[r, c + 1]];
-            let down = grid[[r + 1, c]];
-            if me != right {
-                let edge = (Coord { x: c + 1, y: r }, Coord { x: c + 1, y: r + 1 });
-                ret.entry(me).or_default().push(edge);
-                ret.entry(right).or_default().push(edge);
-            }
-            if me != down {
-                let edge = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
-                ret.entry(me).or_default().push(edge);
-                ret.entry(down).or_default().push(edge);
+    let mut out: Vec<Coord<T>> = Vec::new();
+    for (a, b) in line_segments(ls) {
+        for (x, y) in Bresenham::new((a.x, a.y), (b.x, b.y)) {
+            let c = Coord { x, y };
+            if out.last() != Some(&c) {
+                out.push(c);
             }
         }
     }
-
-    // last column, except bottom right hand cell
-    for r in 0..nrows - 1 {
-        let c = ncols - 1;
-        let me = grid[[r, c]];
-        let down = grid[[r + 1, c]];
-        if me != down {
-            let edge = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
-            ret.entry(me).or_default().push(edge);
-            ret.entry(down).or_default().push(edge);
-        }
-    }
-
-    // last row, except bottom right hand cell
-    for c in 0..ncols - 1 {
-        let r = nrows - 1;
-        let me = grid[[r, c]];
-        let right = grid[[r, c + 1]];
-        if me != right {
-            let edge = (Coord { x: c + 1, y: r }, Coord { x: c + 1, y: r + 1 });
-            ret.entry(me).or_default().push(edge);
-            ret.entry(right).or_default().push(edge);
-        }
-    }
-
-    ret
+    out
 }
 
-/// Converts a collection of unordered grid edges that form a bunch of rings nto a
-/// `MultiLineString`.
-///
-/// This function takes a list of edges, where each edge is represented by a pair
-/// of grid coordinates, and converts them into a `MultiLineString`.  Likely there
-/// will only be a single LineString, but if there are self-intersections multiple
-/// LineStrings are needed.  The edges should completely encircle regions.
-///
-/// # Parameters
+/// Rasterize a `LineString` as a corridor `radius` cells wide on each side
 ///
-/// - `edges`: A vector of edge segments, where each edge is represented as a pair
-///   of `Coord<usize>` values defining the start and end points.
+/// Every cell within Chebyshev distance `radius` of a cell of [`rasterize_linestring`], so
+/// each cell of the thin line is swapped for the square of side `2 * radius + 1` around it.
+/// The ends get square caps.
 ///
 /// # Returns
 ///
-/// A `MultiLineString<usize>` where input edges have been ordered to make a series
-/// of LineStrings.
+/// Each cell once, in the order it is first reached going along the line, the square
+/// around each thin cell being taken row by row.  With `radius` 0 this is exactly
+/// [`rasterize_linestring`], including any cell the line comes back to later.  A negative
+/// `radius` is taken as 0.
 ///
 /// # Examples
 ///
 /// ```
-/// use geo::{Coord, MultiLineString, LineString};
-/// use ndarray::array;
+/// use geo::{Coord, LineString};
 ///
-/// let grid = array![[0]];
-/// let e = geospatial::marching_squares(&grid);
-/// let mls = geospatial::edges_to_multilinestring(0, &e[&0], &grid);
-/// assert_eq!(mls.0.len(), 1);
-/// assert_eq!(mls.0[0], LineString::from(vec![
-///    Coord { x: 0, y: 0 },
-///    Coord { x: 0, y: 1 },
-///    Coord { x: 1, y: 1 },
-///    Coord { x: 1, y: 0 },
-///    Coord { x: 0, y: 0 },
+/// let ls: LineString<i32> = LineString::from(vec![(0, 0), (3, 0)]);
+/// assert_eq!(geospatial::rasterize_linestring_thick(&ls, 0), geospatial::rasterize_linestring(&ls));
+///
+/// // a 6x3 block, no cell twice even though the squares overlap
+/// let cells = geospatial::rasterize_linestring_thick(&ls, 1);
+/// assert_eq!(cells.len(), 18);
+/// assert_eq!(cells[0], Coord { x: -1, y: -1 });
+/// assert!(cells.contains(&Coord { x: 4, y: 1 }));
+///
+/// // a line that doubles back keeps its repeated cells at radius 0
+/// let back: LineString<i32> = LineString::from(vec![(3, -4), (4, -1), (-2, -5), (4, -5)]);
+/// assert_eq!(geospatial::rasterize_linestring_thick(&back, 0), geospatial::rasterize_linestring(&back));
+/// ```
+pub fn rasterize_linestring_thick<T>(ls: &LineString<T>, radius: T) -> Vec<Coord<T>>
+where
+    T: CoordNum + SignedNum + Hash,
+{
+    if radius <= T::zero() {
+        return rasterize_linestring(ls);
+    }
+    let mut seen: LabelSet<Coord<T>> = LabelSet::default();
+    let mut out = Vec::new();
+    for c in rasterize_linestring_iter(ls) {
+        let mut dy = -radius;
+        while dy <= radius {
+            let mut dx = -radius;
+            while dx <= radius {
+                let p = Coord {
+                    x: c.x + dx,
+                    y: c.y + dy,
+                };
+                if seen.insert(p) {
+                    out.push(p);
+                }
+                dx += T::one();
+            }
+            dy += T::one();
+        }
+    }
+    out
+}
+
+/// [`rasterize_linestring`], tagging each cell with the segment that produced it
+///
+/// Segment `i` runs from vertex `i` to vertex `i + 1`, so the tags are the indices of
+/// `ls.0.windows(2)`.  The cells are exactly those of [`rasterize_linestring`].
+///
+/// # Notes
+///
+/// - The cell holding a vertex shared by two segments belongs to the earlier one, the later
+///   segment's copy being the duplicate that [`rasterize_linestring`] drops.
+/// - A line of a single point is one segment of no length, so its cell is tagged 0.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+/// let ls: LineString<i32> = LineString::new(vec![
+///     Coord { x: 0, y: 0 },
+///     Coord { x: 2, y: 0 },
+///     Coord { x: 2, y: 1 },
+/// ]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_indexed(&ls),
+///     vec![
+///         (Coord { x: 0, y: 0 }, 0),
+///         (Coord { x: 1, y: 0 }, 0),
+///         (Coord { x: 2, y: 0 }, 0),
+///         (Coord { x: 2, y: 1 }, 1),
+///     ]
+/// );
+/// ```
+pub fn rasterize_linestring_indexed<T>(ls: &LineString<T>) -> Vec<(Coord<T>, usize)>
+where
+    T: CoordNum + SignedNum,
+{
+    let mut out: Vec<(Coord<T>, usize)> = Vec::new();
+    for (i, (a, b)) in line_segments(ls).enumerate() {
+        for (x, y) in Supercover::new((a.x, a.y), (b.x, b.y)) {
+            let c = Coord { x, y };
+            if out.last().is_none_or(|l| l.0 != c) {
+                out.push((c, i));
+            }
+        }
+    }
+    out
+}
+
+/// Rasterizes a world space `LineString` at a finer resolution, then maps the cells back
+///
+/// Vertices are placed on a grid `factor` times finer than the real one, the line
+/// supercovered there (see [`rasterize_linestring`]), and each fine cell mapped to the real
+/// cell containing it.  Rather than snapping every vertex to a whole cell first, the line
+/// keeps its position to within `1 / factor` of a cell, so a long shallow line steps up
+/// where it actually crosses into the next row instead of where the rounding puts it.
+///
+/// # Parameters
+///
+/// - `ls`: The line, in world coordinates.
+/// - `origin`, `cell`: The grid, as for [`Affine::from_origin_cell`], north up with its top
+///   left corner at `origin` and square cells `cell` wide.
+/// - `factor`: How many sub cells each cell is split into along each axis, `0` is taken
+///   as `1`.
+///
+/// # Returns
+///
+/// The cells as `Coord { x: col, y: row }`, with consecutive duplicates removed.  Cells
+/// outside the grid, left of or above the origin, come out negative.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// // drops into row 1 part way through column 1
+/// let ls = LineString::new(vec![Coord { x: 0.9, y: -0.9 }, Coord { x: 3.9, y: -1.2 }]);
+/// let origin = Coord { x: 0.0, y: 0.0 };
+/// assert_eq!(
+///     geospatial::rasterize_linestring_supersample(&ls, origin, 1.0, 16),
+///     vec![
+///         Coord { x: 0, y: 0 },
+///         Coord { x: 1, y: 0 },
+///         Coord { x: 1, y: 1 },
+///         Coord { x: 2, y: 1 },
+///         Coord { x: 3, y: 1 },
+///     ]
+/// );
+/// // without supersampling the vertices snap to (0, 0) and (3, 1), and the line cuts the
+/// // corner
+/// assert_eq!(
+///     geospatial::rasterize_linestring_supersample(&ls, origin, 1.0, 1),
+///     vec![
+///         Coord { x: 0, y: 0 },
+///         Coord { x: 1, y: 0 },
+///         Coord { x: 2, y: 1 },
+///         Coord { x: 3, y: 1 },
+///     ]
+/// );
+/// ```
+pub fn rasterize_linestring_supersample(
+    ls: &LineString<f64>,
+    origin: Coord<f64>,
+    cell: f64,
+    factor: u32,
+) -> Vec<Coord<isize>> {
+    let factor = factor.max(1) as isize;
+    let scale = factor as f64 / cell;
+    let fine: LineString<isize> =
+        ls.0.iter()
+            .map(|p| Coord {
+                x: ((p.x - origin.x) * scale).floor() as isize,
+                y: ((origin.y - p.y) * scale).floor() as isize,
+            })
+            .collect();
+    let mut out = Vec::new();
+    for c in rasterize_linestring(&fine) {
+        let c = Coord {
+            x: c.x.div_euclid(factor),
+            y: c.y.div_euclid(factor),
+        };
+        if Some(&c) != out.last() {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// The cells a floating point line passes through, with where along it each is entered
+///
+/// For profiles along a path, values sampled from each cell can be placed at the distance
+/// the line reaches it.  This walks the exact grid crossings of each segment, rather than
+/// snapping the vertices to cells first.
+///
+/// # Parameters
+///
+/// - `ls`: The line, in grid units where cell `(col, row)` covers `col <= x < col + 1` and
+///   `row <= y < row + 1`.
+///
+/// # Returns
+///
+/// Each cell entered, as `Coord { x: col, y: row }`, with the distance along the line at
+/// which it is entered.  The first cell is at `0.0` and the distances carry on across
+/// vertices, so they strictly increase.  An empty line gives no cells.
+///
+/// # Notes
+///
+/// - A point on a cell boundary is in the cell the line goes on into.  So a vertex on a
+///   boundary where the line turns back doesn't count as entering the cell beyond.
+/// - Where the line passes exactly through a grid corner it steps diagonally, the two
+///   cells either side of the corner are only touched at a point and are left out.
+/// - Distances are euclidean in grid units.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let ls = LineString::new(vec![Coord { x: 0.5, y: 0.5 }, Coord { x: 2.5, y: 0.5 }, Coord { x: 2.5, y: 1.5 }]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_crossings(&ls),
+///     vec![
+///         (Coord { x: 0, y: 0 }, 0.0),
+///         (Coord { x: 1, y: 0 }, 0.5),
+///         (Coord { x: 2, y: 0 }, 1.5),
+///         (Coord { x: 2, y: 1 }, 2.5),
+///     ]
+/// );
+/// ```
+pub fn rasterize_linestring_crossings(ls: &LineString<f64>) -> Vec<(Coord<isize>, f64)> {
+    let mut out: Vec<(Coord<isize>, f64)> = Vec::new();
+    let Some(first) = ls.0.first() else {
+        return out;
+    };
+
+    // a cell the line leaves as soon as it enters, like at a corner just missed by
+    // rounding, has no length in it and is replaced
+    let enter = |out: &mut Vec<(Coord<isize>, f64)>, cell: Coord<isize>, at: f64| {
+        if let Some(last) = out.last()
+            && last.1 >= at
+        {
+            out.pop();
+        }
+        if out.last().map(|(c, _)| c) != Some(&cell) {
+            out.push((cell, at));
+        }
+    };
+    // the cell just along the direction d from v on one axis
+    let start = |v: f64, d: f64| {
+        if d < 0.0 {
+            v.ceil() as isize - 1
+        } else {
+            v.floor() as isize
+        }
+    };
+    let mut done = 0.0;
+    for w in ls.0.windows(2) {
+        let (p, q) = (w[0], w[1]);
+        let (dx, dy) = (q.x - p.x, q.y - p.y);
+        let len = dx.hypot(dy);
+        if len == 0.0 {
+            continue;
+        }
+        let mut cell = Coord {
+            x: start(p.x, dx),
+            y: start(p.y, dy),
+        };
+        enter(&mut out, cell, done);
+
+        // fraction of the segment at which the next boundary on an axis is crossed
+        let next = |v: f64, d: f64, c: isize| {
+            if d > 0.0 {
+                ((c + 1) as f64 - v) / d
+            } else if d < 0.0 {
+                (c as f64 - v) / d
+            } else {
+                f64::INFINITY
+            }
+        };
+        loop {
+            let (tx, ty) = (next(p.x, dx, cell.x), next(p.y, dy, cell.y));
+            let t = tx.min(ty);
+            if t >= 1.0 {
+                break;
+            }
+            if tx <= ty {
+                cell.x += dx.signum() as isize;
+            }
+            if ty <= tx {
+                cell.y += dy.signum() as isize;
+            }
+            enter(&mut out, cell, done + t * len);
+        }
+        done += len;
+    }
+    if out.is_empty() {
+        let cell = Coord {
+            x: first.x.floor() as isize,
+            y: first.y.floor() as isize,
+        };
+        out.push((cell, 0.0));
+    }
+    out
+}
+
+/// A grid of how far along a world coordinate line each cell is first reached
+///
+/// [`rasterize_linestring_crossings`] on a georeferenced grid, put straight into an
+/// array for corridor analysis.
+///
+/// # Parameters
+///
+/// - `ls`: The line, in world coordinates.
+/// - `shape`: The grid's `(rows, cols)`.
+/// - `origin`, `cell`: The grid, as for [`Affine::from_origin_cell`], north up with its top
+///   left corner at `origin` and square cells `cell` wide.
+///
+/// # Returns
+///
+/// An array of `shape` with each cell the line passes through holding the distance along
+/// the line, in world units, at which it first enters the cell.  Every other cell is
+/// `NaN`.  Parts of the line off the grid are skipped, but still count towards the
+/// distance.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// // east along the middle of the second row, 10 units to a cell
+/// let ls = LineString::new(vec![Coord { x: 105.0, y: 35.0 }, Coord { x: 125.0, y: 35.0 }]);
+/// let d = geospatial::rasterize_to_distance_grid(&ls, (3, 3), Coord { x: 100.0, y: 50.0 }, 10.0);
+/// assert_eq!(d.row(1).to_vec(), vec![0.0, 5.0, 15.0]);
+/// assert!(d.row(0).iter().all(|v| v.is_nan()));
+/// ```
+pub fn rasterize_to_distance_grid(
+    ls: &LineString<f64>,
+    shape: (usize, usize),
+    origin: Coord<f64>,
+    cell: f64,
+) -> Array2<f64> {
+    let grid_ls: LineString<f64> =
+        ls.0.iter()
+            .map(|p| Coord {
+                x: (p.x - origin.x) / cell,
+                y: (origin.y - p.y) / cell,
+            })
+            .collect();
+    let mut out = Array2::from_elem(shape, f64::NAN);
+    for (c, t) in rasterize_linestring_crossings(&grid_ls) {
+        if c.x < 0 || c.y < 0 {
+            continue;
+        }
+        if let Some(v) = out.get_mut([c.y as usize, c.x as usize])
+            && v.is_nan()
+        {
+            *v = t * cell;
+        }
+    }
+    out
+}
+
+/// [`rasterize_linestring`] for a line in world coordinates
+///
+/// Each vertex is snapped to the grid cell it is in, and the supercover traversal is run
+/// between the snapped cells.
+///
+/// # Parameters
+///
+/// - `ls`: The line, in world coordinates.  Can be empty.
+/// - `cell`: The width of a square cell, in world units.
+/// - `origin`: The grid, as for [`Affine::from_origin_cell`], north up with its top left
+///   corner at `origin`.  Column `x` covers `origin.x + x * cell` up to
+///   `origin.x + (x + 1) * cell`, and row `y` covers `origin.y - y * cell` down to
+///   `origin.y - (y + 1) * cell`.
+///
+/// # Returns
+///
+/// The cells, as for [`rasterize_linestring`].  Cells left of or above `origin` have
+/// negative coordinates.
+///
+/// # Notes
+///
+/// - Vertices are rounded down, so a vertex exactly on a boundary between cells is in the
+///   cell with the larger index, and half a cell left of or above the origin is in cell
+///   `-1`.
+/// - Only the vertices are snapped, the cells between them are those of the line between
+///   the snapped cells.  A line that clips the corner of a cell between vertices may miss
+///   it, use [`rasterize_linestring_crossings`] to follow the line exactly.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let ls = LineString::new(vec![Coord { x: -5.0, y: -25.0 }, Coord { x: 20.0, y: -25.0 }]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_f64(&ls, 10.0, Coord { x: 0.0, y: 0.0 }),
+///     vec![
+///         Coord { x: -1, y: 2 },
+///         Coord { x: 0, y: 2 },
+///         Coord { x: 1, y: 2 },
+///         Coord { x: 2, y: 2 },
+///     ]
+/// );
+/// // the same cells as supersampling without splitting them
+/// let ls = LineString::new(vec![Coord { x: 3.0, y: -1.0 }, Coord { x: 38.0, y: -12.0 }]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_f64(&ls, 10.0, Coord { x: 0.0, y: 0.0 }),
+///     geospatial::rasterize_linestring_supersample(&ls, Coord { x: 0.0, y: 0.0 }, 10.0, 1)
+/// );
+/// let empty: LineString<f64> = LineString::new(vec![]);
+/// assert!(geospatial::rasterize_linestring_f64(&empty, 10.0, Coord { x: 0.0, y: 0.0 }).is_empty());
+/// ```
+pub fn rasterize_linestring_f64(
+    ls: &LineString<f64>,
+    cell: f64,
+    origin: Coord<f64>,
+) -> Vec<Coord<isize>> {
+    let snapped: LineString<isize> =
+        ls.0.iter()
+            .map(|p| Coord {
+                x: ((p.x - origin.x) / cell).floor() as isize,
+                y: ((origin.y - p.y) / cell).floor() as isize,
+            })
+            .collect();
+    rasterize_linestring(&snapped)
+}
+
+/// Share a quantity carried by a line out over the cells it crosses, by length
+///
+/// Each cell gets the part of `total` in proportion to how much of the line is inside it,
+/// so the quantity is conserved, as wanted for flows.  Cells and their boundaries are as
+/// in [`rasterize_linestring_crossings`].
+///
+/// # Returns
+///
+/// Each cell once, in the order the line first enters it, with its share.  The shares sum
+/// to `total`, up to rounding.  A line of no length puts all of `total` in its one cell,
+/// and an empty line gives nothing.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// // a quarter of the line in column 0, half in column 1, the rest in column 2
+/// let ls = LineString::new(vec![Coord { x: 0.5, y: 0.5 }, Coord { x: 2.5, y: 0.5 }]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_weighted(&ls, 8.0),
+///     vec![
+///         (Coord { x: 0, y: 0 }, 2.0),
+///         (Coord { x: 1, y: 0 }, 4.0),
+///         (Coord { x: 2, y: 0 }, 2.0),
+///     ]
+/// );
+/// ```
+pub fn rasterize_linestring_weighted(ls: &LineString<f64>, total: f64) -> Vec<(Coord<isize>, f64)> {
+    let crossings = rasterize_linestring_crossings(ls);
+    let length: f64 =
+        ls.0.windows(2)
+            .map(|w| (w[1].x - w[0].x).hypot(w[1].y - w[0].y))
+            .sum();
+
+    let mut out: Vec<(Coord<isize>, f64)> = Vec::new();
+    let mut slot: HashMap<Coord<isize>, usize> = HashMap::new();
+    for (i, (cell, t)) in crossings.iter().enumerate() {
+        let end = crossings.get(i + 1).map_or(length, |c| c.1);
+        let share = if length > 0.0 {
+            total * (end - t) / length
+        } else {
+            total
+        };
+        let k = *slot.entry(*cell).or_insert_with(|| {
+            out.push((*cell, 0.0));
+            out.len() - 1
+        });
+        out[k].1 += share;
+    }
+    out
+}
+
+/// The cells a floating point line passes through, with the length of line inside each
+///
+/// The gaps between the entry distances of [`rasterize_linestring_crossings`], so cells
+/// can be weighted by how far the line travels through them, as for flow accumulation.
+/// Cells and their boundaries are as in [`rasterize_linestring_crossings`].
+///
+/// # Returns
+///
+/// Each pass through a cell, in order along the line, with the length of that pass.  A
+/// line that comes back to a cell has it listed again, [`rasterize_linestring_weighted`]
+/// with the line's length as `total` gives one total per cell instead.  The lengths sum
+/// to the length of the line, up to rounding.  A line of no length gives its one cell
+/// with length 0, and an empty line nothing.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let ls = LineString::new(vec![Coord { x: 0.5, y: 0.5 }, Coord { x: 2.5, y: 0.5 }, Coord { x: 2.5, y: 1.5 }]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_segments(&ls),
+///     vec![
+///         (Coord { x: 0, y: 0 }, 0.5),
+///         (Coord { x: 1, y: 0 }, 1.0),
+///         (Coord { x: 2, y: 0 }, 1.0),
+///         (Coord { x: 2, y: 1 }, 0.5),
+///     ]
+/// );
+/// ```
+pub fn rasterize_linestring_segments(ls: &LineString<f64>) -> Vec<(Coord<isize>, f64)> {
+    let crossings = rasterize_linestring_crossings(ls);
+    let length: f64 =
+        ls.0.windows(2)
+            .map(|w| (w[1].x - w[0].x).hypot(w[1].y - w[0].y))
+            .sum();
+    crossings
+        .iter()
+        .enumerate()
+        .map(|(i, (cell, t))| {
+            let end = crossings.get(i + 1).map_or(length, |c| c.1);
+            (*cell, end - t)
+        })
+        .collect()
+}
+
+/// The cells both `LineString`s pass through
+///
+/// The same as rasterizing both with [`rasterize_linestring`] and intersecting the cells,
+/// but pairs of segments whose bounding boxes overlap are found by a sweep across x, and
+/// only those segments are rasterized and compared, within the overlap.  Lines that only
+/// meet in a few places, or that have long segments, come out well ahead of intersecting
+/// two sets of cells, and lines that wander back and forth over the same ground are about
+/// even with it.
+///
+/// # Returns
+///
+/// The shared cells sorted by `(y, x)`, each once.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let a: LineString<i32> = LineString::from(vec![(0, 0), (10, 0), (10, 10)]);
+/// let b: LineString<i32> = LineString::from(vec![(5, -5), (5, 5), (20, 5)]);
+/// assert_eq!(
+///     geospatial::rasterize_intersection(&a, &b),
+///     vec![Coord { x: 5, y: 0 }, Coord { x: 10, y: 5 }]
+/// );
+/// ```
+pub fn rasterize_intersection<T>(a: &LineString<T>, b: &LineString<T>) -> Vec<Coord<T>>
+where
+    T: CoordNum + SignedNum,
+{
+    // a segment's ends and the corners of its bounding box, which the supercover never
+    // leaves
+    type Segment<U> = (Coord<U>, Coord<U>, Coord<U>, Coord<U>);
+    let segments = |ls: &LineString<T>| -> Vec<Segment<T>> {
+        line_segments(ls)
+            .map(|(a, b)| {
+                let lo = Coord {
+                    x: a.x.min(b.x),
+                    y: a.y.min(b.y),
+                };
+                let hi = Coord {
+                    x: a.x.max(b.x),
+                    y: a.y.max(b.y),
+                };
+                (a, b, lo, hi)
+            })
+            .collect()
+    };
+    let (sa, sb) = (segments(a), segments(b));
+    let key = |c: &Coord<T>| (c.y, c.x);
+    // each segment's cells sorted by (y, x), made the first time it is needed
+    let cells = |(p, q, _, _): Segment<T>| -> Vec<Coord<T>> {
+        let mut cells: Vec<Coord<T>> = Supercover::new((p.x, p.y), (q.x, q.y))
+            .map(|(x, y)| Coord { x, y })
+            .collect();
+        cells.sort_by_key(key);
+        cells
+    };
+    let mut cache_a: Vec<Option<Vec<Coord<T>>>> = vec![None; sa.len()];
+    let mut cache_b: Vec<Option<Vec<Coord<T>>>> = vec![None; sb.len()];
+
+    // sweep across x, each segment meeting those of the other line whose boxes are still open
+    let mut order: Vec<(bool, usize)> = (0..sa.len())
+        .map(|i| (false, i))
+        .chain((0..sb.len()).map(|j| (true, j)))
+        .collect();
+    let lo_x = |&(in_b, k): &(bool, usize)| if in_b { sb[k].2.x } else { sa[k].2.x };
+    order.sort_by_key(lo_x);
+    let (mut open_a, mut open_b): (Vec<usize>, Vec<usize>) = (Vec::new(), Vec::new());
+
+    let mut out: Vec<Coord<T>> = Vec::new();
+    for &(in_b, k) in &order {
+        let (own, other) = if in_b { (&sb, &sa) } else { (&sa, &sb) };
+        let (_, _, alo, ahi) = own[k];
+        let open = if in_b { &mut open_a } else { &mut open_b };
+        open.retain(|&o| other[o].3.x >= alo.x);
+        for &o in open.iter() {
+            let (i, j) = if in_b { (o, k) } else { (k, o) };
+            let (_, _, blo, bhi) = other[o];
+            if alo.y.max(blo.y) > ahi.y.min(bhi.y) {
+                continue;
+            }
+            let lo = Coord {
+                x: alo.x.max(blo.x),
+                y: alo.y.max(blo.y),
+            };
+            let hi = Coord {
+                x: ahi.x.min(bhi.x),
+                y: ahi.y.min(bhi.y),
+            };
+            // the rows of the overlap, then the columns
+            let rows = |cells: &[Coord<T>]| -> (usize, usize) {
+                (
+                    cells.partition_point(|c| c.y < lo.y),
+                    cells.partition_point(|c| c.y <= hi.y),
+                )
+            };
+            let ca = cache_a[i].get_or_insert_with(|| cells(sa[i]));
+            let cb = cache_b[j].get_or_insert_with(|| cells(sb[j]));
+            let ((a0, a1), (b0, b1)) = (rows(ca), rows(cb));
+            let cb = &cb[b0..b1];
+            out.extend(
+                ca[a0..a1]
+                    .iter()
+                    .filter(|c| lo.x <= c.x && c.x <= hi.x)
+                    .filter(|c| cb.binary_search_by_key(&key(c), key).is_ok()),
+            );
+        }
+        if in_b {
+            open_b.push(k);
+        } else {
+            open_a.push(k);
+        }
+    }
+    out.sort_by_key(|c| (c.y, c.x));
+    out.dedup();
+    out
+}
+
+// offsets of the first octant of a midpoint circle, (x, y) with x >= y >= 0
+fn circle_octant(radius: isize) -> Vec<(isize, isize)> {
+    let (mut x, mut y, mut err) = (radius, 0, 1 - radius);
+    let mut out = Vec::new();
+    while x >= y {
+        out.push((x, y));
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+    out
+}
+
+/// Rasterizes the outline of a circle with the midpoint circle algorithm
+///
+/// One octant is computed and reflected into the other seven, so the outline is exactly
+/// 8-fold symmetric, 8-connected and has no duplicate cells.
+///
+/// # Returns
+///
+/// The cells in order around the circle, starting at `(center.x + radius, center.y)` and
+/// heading towards increasing `y`.  A `radius` of 0 gives just the center, and a negative
+/// one nothing.
+///
+/// # Examples
+/// ```
+/// use geo::Coord;
+///
+/// let c = Coord { x: 10, y: 10 };
+/// let cells = geospatial::rasterize_circle(c, 2);
+/// assert_eq!(cells, vec![
+///     Coord { x: 12, y: 10 },
+///     Coord { x: 12, y: 11 },
+///     Coord { x: 11, y: 12 },
+///     Coord { x: 10, y: 12 },
+///     Coord { x: 9, y: 12 },
+///     Coord { x: 8, y: 11 },
+///     Coord { x: 8, y: 10 },
+///     Coord { x: 8, y: 9 },
+///     Coord { x: 9, y: 8 },
+///     Coord { x: 10, y: 8 },
+///     Coord { x: 11, y: 8 },
+///     Coord { x: 12, y: 9 },
+/// ]);
+/// assert_eq!(geospatial::rasterize_circle(c, 0), vec![c]);
+/// assert_eq!(geospatial::rasterize_circle(c, 5).len(), 28);
+/// ```
+pub fn rasterize_circle(center: Coord<isize>, radius: isize) -> Vec<Coord<isize>> {
+    if radius < 0 {
+        return vec![];
+    }
+    let mut offsets: Vec<(isize, isize)> = circle_octant(radius)
+        .into_iter()
+        .flat_map(|(x, y)| {
+            [
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ]
+        })
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    // by angle from +x, exactly: the upper half plane first, then by cross product
+    offsets.sort_by(|&(ax, ay), &(bx, by)| {
+        let lower = |x: isize, y: isize| y < 0 || (y == 0 && x < 0);
+        lower(ax, ay)
+            .cmp(&lower(bx, by))
+            .then_with(|| (bx * ay).cmp(&(ax * by)))
+    });
+    offsets
+        .into_iter()
+        .map(|(x, y)| Coord {
+            x: center.x + x,
+            y: center.y + y,
+        })
+        .collect()
+}
+
+/// Rasterizes a filled circle
+///
+/// Every cell on or inside the outline of [`rasterize_circle`], row by row from the top.
+///
+/// # Examples
+/// ```
+/// use geo::Coord;
+///
+/// let c = Coord { x: 0, y: 0 };
+/// assert_eq!(geospatial::rasterize_disk(c, 1).len(), 5);
+/// assert_eq!(geospatial::rasterize_disk(c, 0), vec![c]);
+/// let disk = geospatial::rasterize_disk(c, 5);
+/// assert!(geospatial::rasterize_circle(c, 5).iter().all(|p| disk.contains(p)));
+/// assert_eq!(disk.len(), 97);
+/// ```
+pub fn rasterize_disk(center: Coord<isize>, radius: isize) -> Vec<Coord<isize>> {
+    if radius < 0 {
+        return vec![];
+    }
+    // the half width of each row, from the widest outline cell in it
+    let mut half = vec![0isize; radius as usize + 1];
+    for (x, y) in circle_octant(radius) {
+        half[y as usize] = half[y as usize].max(x);
+        half[x as usize] = half[x as usize].max(y);
+    }
+    (-radius..=radius)
+        .flat_map(|dy| {
+            let w = half[dy.unsigned_abs()];
+            (-w..=w).map(move |dx| Coord {
+                x: center.x + dx,
+                y: center.y + dy,
+            })
+        })
+        .collect()
+}
+
+/// Converts a geometry from cell corner coordinates to cell index coordinates
+///
+/// See the [coordinate conventions](crate#coordinate-conventions).  The center of cell
+/// `[r, c]` is `(c + 0.5, r + 0.5)` on the corner lattice and `(c, r)` as an index, so this
+/// subtracts a half from every coordinate.  Works on anything `geo` can map the coordinates
+/// of, like a `Point`, `LineString` or `Polygon`.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{LineString, Point};
+///
+/// // the boundary of cell [0, 0] goes round its center at (0, 0)
+/// let ring = LineString::from(vec![(0, 0), (1, 0), (1, 1), (0, 1), (0, 0)]);
+/// assert_eq!(
+///     geospatial::corners_to_centers(&ring),
+///     LineString::from(vec![(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5), (-0.5, -0.5)])
+/// );
+/// assert_eq!(geospatial::corners_to_centers(&Point::new(2.5, 3.5)), Point::new(2.0, 3.0));
+/// ```
+pub fn corners_to_centers<G, T>(g: &G) -> G::Output
+where
+    G: MapCoords<T, f64>,
+    T: CoordNum,
+{
+    g.map_coords(|c| Coord {
+        x: c.x.to_f64().unwrap() - 0.5,
+        y: c.y.to_f64().unwrap() - 0.5,
+    })
+}
+
+/// Converts a geometry from cell index coordinates to cell corner coordinates
+///
+/// The opposite of [`corners_to_centers`], adding a half to every coordinate, so a
+/// rasterized path can be drawn over the output of [`marching_squares`].
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let cells: Vec<Coord<isize>> = vec![Coord { x: 0, y: 0 }, Coord { x: 1, y: 0 }];
+/// assert_eq!(
+///     geospatial::centers_to_corners(&LineString::new(cells)),
+///     LineString::from(vec![(0.5, 0.5), (1.5, 0.5)])
+/// );
+/// ```
+pub fn centers_to_corners<G, T>(g: &G) -> G::Output
+where
+    G: MapCoords<T, f64>,
+    T: CoordNum,
+{
+    g.map_coords(|c| Coord {
+        x: c.x.to_f64().unwrap() + 0.5,
+        y: c.y.to_f64().unwrap() + 0.5,
+    })
+}
+
+/// A side or corner of a grid cell, see [`rasterize_linestring_faces`]
+///
+/// North is towards row 0 (decreasing `y`), East towards increasing `x`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    North,
+    South,
+    East,
+    West,
+    /// the line passes exactly through a corner and moves diagonally, which only happens
+    /// when it hits a lattice point
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+    /// the line starts or ends inside the cell
+    Interior,
+}
+
+impl Face {
+    // the face of `from` that is crossed moving to the neighbouring cell `to`
+    fn between<T: CoordNum + SignedNum>(from: Coord<T>, to: Coord<T>) -> Face {
+        let zero = T::zero();
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        match (dx > zero, dx < zero, dy > zero, dy < zero) {
+            (true, _, true, _) => Face::SouthEast,
+            (true, _, _, true) => Face::NorthEast,
+            (_, true, true, _) => Face::SouthWest,
+            (_, true, _, true) => Face::NorthWest,
+            (true, ..) => Face::East,
+            (_, true, ..) => Face::West,
+            (_, _, true, _) => Face::South,
+            (_, _, _, true) => Face::North,
+            _ => Face::Interior,
+        }
+    }
+}
+
+/// Rasterizes a `LineString`, giving the face of each cell where the line enters and leaves
+///
+/// The cells are exactly those of [`rasterize_linestring`], each paired with the face it is
+/// entered through and the face it is left through.  The first cell is entered, and the
+/// last left, from the [`Face::Interior`].  This is what routing along the rasterized path
+/// needs, eg to know which side of a cell water flows in from.
+///
+/// # Notes
+///
+/// - Where the line goes exactly through a cell corner the supercover steps diagonally, and
+///   that is reported as a corner, eg [`Face::SouthEast`], rather than picking one side.
+/// - A vertex of the `LineString` can turn the line around inside a cell, so the entry and
+///   exit faces can be the same.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+/// use geospatial::Face;
+///
+/// let ls: LineString<i32> = LineString::new(vec![
+///     Coord { x: 0, y: 0 },
+///     Coord { x: 2, y: 0 },
+///     Coord { x: 2, y: 1 },
+/// ]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_faces(&ls),
+///     vec![
+///         (Coord { x: 0, y: 0 }, Face::Interior, Face::East),
+///         (Coord { x: 1, y: 0 }, Face::West, Face::East),
+///         (Coord { x: 2, y: 0 }, Face::West, Face::South),
+///         (Coord { x: 2, y: 1 }, Face::North, Face::Interior),
+///     ]
+/// );
+///
+/// // straight through a corner
+/// let ls: LineString<i32> = LineString::new(vec![Coord { x: 0, y: 0 }, Coord { x: 1, y: -1 }]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_faces(&ls),
+///     vec![
+///         (Coord { x: 0, y: 0 }, Face::Interior, Face::NorthEast),
+///         (Coord { x: 1, y: -1 }, Face::SouthWest, Face::Interior),
+///     ]
+/// );
+/// ```
+pub fn rasterize_linestring_faces<T>(ls: &LineString<T>) -> Vec<(Coord<T>, Face, Face)>
+where
+    T: CoordNum + SignedNum,
+{
+    let cells = rasterize_linestring(ls);
+    (0..cells.len())
+        .map(|i| {
+            let entry = match i {
+                0 => Face::Interior,
+                _ => Face::between(cells[i], cells[i - 1]),
+            };
+            let exit = match cells.get(i + 1) {
+                Some(&next) => Face::between(cells[i], next),
+                None => Face::Interior,
+            };
+            (cells[i], entry, exit)
+        })
+        .collect()
+}
+
+/// Errors from [`try_rasterize_linestring`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RasterizeError {
+    /// the segment starting at this vertex would overflow the coordinate type
+    Overflow { segment: usize },
+}
+
+impl std::fmt::Display for RasterizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RasterizeError::Overflow { segment } => {
+                write!(f, "segment {segment} overflows the coordinate type")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RasterizeError {}
+
+/// [`rasterize_linestring`], returning an error rather than wrong cells on overflow
+///
+/// Every segment is checked before rasterizing, working in `i128`, to make sure the
+/// difference between its vertices, and the step past its last cell, fit in `T`.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+/// use geospatial::RasterizeError;
+///
+/// let ls: LineString<i8> = LineString::new(vec![
+///     Coord { x: 0, y: 0 },
+///     Coord { x: 2, y: 1 },
+/// ]);
+/// assert_eq!(
+///     geospatial::try_rasterize_linestring(&ls),
+///     Ok(geospatial::rasterize_linestring(&ls))
+/// );
+///
+/// let ls: LineString<i8> = LineString::new(vec![
+///     Coord { x: 0, y: 0 },
+///     Coord { x: 2, y: 1 },
+///     Coord { x: -100, y: 1 },
+///     Coord { x: 100, y: 1 },
+/// ]);
+/// assert_eq!(
+///     geospatial::try_rasterize_linestring(&ls),
+///     Err(RasterizeError::Overflow { segment: 2 })
+/// );
+///
+/// let ls: LineString<i8> = LineString::new(vec![
+///     Coord { x: 0, y: 0 },
+///     Coord { x: 127, y: 0 },
+/// ]);
+/// assert_eq!(
+///     geospatial::try_rasterize_linestring(&ls),
+///     Err(RasterizeError::Overflow { segment: 0 })
+/// );
+/// ```
+pub fn try_rasterize_linestring<T>(ls: &LineString<T>) -> Result<Vec<Coord<T>>, RasterizeError>
+where
+    T: CoordNum + SignedNum,
+{
+    let fits = |v: i128| T::from(v).is_some();
+    for (segment, w) in ls.0.windows(2).enumerate() {
+        let ok = [(w[0].x, w[1].x), (w[0].y, w[1].y)].iter().all(|(a, b)| {
+            let (a, b) = (a.to_i128().unwrap(), b.to_i128().unwrap());
+            fits(b - a) && fits(b + (b - a).signum())
+        });
+        if !ok {
+            return Err(RasterizeError::Overflow { segment });
+        }
+    }
+    Ok(rasterize_linestring(ls))
+}
+
+/// [`rasterize_linestring`], shifted so `origin` is cell `(0, 0)`
+///
+/// Rasterizes in the line's own coordinates, so the trace is exactly that of
+/// [`rasterize_linestring`], and then subtracts `origin` from every cell.  Use the grid's
+/// top left cell as `origin` and the cells can index an `Array2` directly (as `[y, x]`).
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let ls: LineString<i32> = LineString::from(vec![(-500, 20), (-497, 20)]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_offset(&ls, Coord { x: -500, y: 18 }),
+///     vec![
+///         Coord { x: 0, y: 2 },
+///         Coord { x: 1, y: 2 },
+///         Coord { x: 2, y: 2 },
+///         Coord { x: 3, y: 2 },
+///     ]
+/// );
+/// ```
+pub fn rasterize_linestring_offset<T>(ls: &LineString<T>, origin: Coord<T>) -> Vec<Coord<T>>
+where
+    T: CoordNum + SignedNum,
+{
+    rasterize_linestring(ls)
+        .into_iter()
+        .map(|c| c - origin)
+        .collect()
+}
+
+/// Rasterizes a `LineString` onto a toroidal grid, wrapping cells that fall off one side
+/// back onto the other.
+///
+/// The line is rasterized as if the grid were infinite, and then every cell is wrapped
+/// modulo `width` and `height`, so a segment that runs off the right edge keeps going from
+/// the left edge without any gap.  The vertices don't have to be inside the grid.
+///
+/// # Parameters
+///
+/// - `ls`: The `LineString` to rasterize.
+/// - `width`, `height`: The grid dimensions, x wraps at `width` and y at `height`.
+///
+/// # Returns
+///
+/// The cells the line passes through, all with `0 <= x < width` and `0 <= y < height`.
+///
+/// # Notes
+///
+/// - Wrapping uses the euclidean remainder, so an endpoint exactly on the boundary, at
+///   `x == width` say, is the same cell as `x == 0`, and `x == -1` is `x == width - 1`.
+/// - To cross a boundary, give the segment's vertices in unwrapped coordinates, eg
+///   `(8, 0)` to `(12, 0)` on a width 10 grid.  A segment from `(8, 0)` to `(2, 0)` is
+///   rasterized the short way across the inside of the grid, not across the seam.
+/// - Consecutive duplicate cells, which can happen after wrapping on narrow grids, are
+///   removed.
+///
+/// # Panics
+///
+/// If `width` or `height` is not positive.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+/// let ls: LineString<i32> = LineString::new(vec![
+///     Coord { x: 8, y: 1 },
+///     Coord { x: 11, y: 1 },
+/// ]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_wrapped(&ls, 10, 4),
+///     vec![
+///         Coord {x:8,y:1},
+///         Coord {x:9,y:1},
+///         Coord {x:0,y:1},
+///         Coord {x:1,y:1},
+///     ]
+/// );
+/// // negative coordinates wrap too
+/// let ls: LineString<i32> = LineString::new(vec![
+///     Coord { x: 0, y: 0 },
+///     Coord { x: 0, y: -2 },
+/// ]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_wrapped(&ls, 10, 4),
+///     vec![Coord {x:0,y:0}, Coord {x:0,y:3}, Coord {x:0,y:2}]
+/// );
+/// ```
+pub fn rasterize_linestring_wrapped<T>(ls: &LineString<T>, width: T, height: T) -> Vec<Coord<T>>
+where
+    T: CoordNum + SignedNum,
+{
+    assert!(
+        width > T::zero() && height > T::zero(),
+        "grid dimensions must be positive"
+    );
+    let wrap = |v: T, n: T| {
+        let m = v % n;
+        if m < T::zero() { m + n } else { m }
+    };
+    let mut out = Vec::new();
+    for c in rasterize_linestring(ls) {
+        let c = Coord {
+            x: wrap(c.x, width),
+            y: wrap(c.y, height),
+        };
+        if Some(&c) != out.last() {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// the Cohen-Sutherland outcode of a cell against the grid [0, width) x [0, height)
+fn outcode<T: CoordNum>(c: Coord<T>, width: T, height: T) -> u8 {
+    let mut code = 0;
+    if c.x < T::zero() {
+        code |= 1;
+    } else if c.x >= width {
+        code |= 2;
+    }
+    if c.y < T::zero() {
+        code |= 4;
+    } else if c.y >= height {
+        code |= 8;
+    }
+    code
+}
+
+/// [`rasterize_linestring`], keeping only the cells inside a `width` by `height` grid
+///
+/// Every cell returned has `0 <= x < width` and `0 <= y < height`, so it can index an
+/// `Array2` of shape `(height, width)` without checking.  The bounds are exclusive, `width`
+/// and `height` are the grid's dimensions rather than its last cell.
+///
+/// # Returns
+///
+/// The in-bounds cells of [`rasterize_linestring`], in the same order.  A line that leaves
+/// the grid and comes back just skips the cells outside.
+///
+/// # Notes
+///
+/// - Each segment is classified with Cohen-Sutherland outcodes first.  A segment with both
+///   ends off the same side of the grid can't reach it and is skipped without being
+///   traced, and one with both ends inside is kept whole.  Only a segment crossing the
+///   border is traced and has its outside cells dropped, so the in-bounds part is exactly
+///   the cells [`rasterize_linestring`] gives it, not a retrace from a moved endpoint.
+/// - A non-positive `width` or `height` gives nothing.
+///
+/// # Examples
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let ls: LineString<i32> = LineString::from(vec![(-2, 1), (2, 1), (2, -5), (-3, -6)]);
+/// assert_eq!(
+///     geospatial::rasterize_linestring_clipped(&ls, 3, 2),
+///     vec![
+///         Coord { x: 0, y: 1 },
+///         Coord { x: 1, y: 1 },
+///         Coord { x: 2, y: 1 },
+///         Coord { x: 2, y: 0 },
+///     ]
+/// );
+/// // entirely outside
+/// let ls: LineString<i32> = LineString::from(vec![(5, 0), (9, 1)]);
+/// assert!(geospatial::rasterize_linestring_clipped(&ls, 3, 2).is_empty());
+/// ```
+pub fn rasterize_linestring_clipped<T>(ls: &LineString<T>, width: T, height: T) -> Vec<Coord<T>>
+where
+    T: CoordNum + SignedNum,
+{
+    let mut out = Vec::new();
+    if width <= T::zero() || height <= T::zero() {
+        return out;
+    }
+    // the last cell traced, in or out of the grid, so a corner isn't repeated
+    let mut last: Option<Coord<T>> = None;
+    for (a, b) in line_segments(ls) {
+        let (ca, cb) = (outcode(a, width, height), outcode(b, width, height));
+        if ca & cb != 0 {
+            last = None;
+            continue;
+        }
+        for (x, y) in Supercover::new((a.x, a.y), (b.x, b.y)) {
+            let c = Coord { x, y };
+            if last == Some(c) {
+                continue;
+            }
+            last = Some(c);
+            if (ca | cb) == 0 || outcode(c, width, height) == 0 {
+                out.push(c);
+            }
+        }
+    }
+    out
+}
+
+/// A boolean grid packed 64 cells to a `u64`
+///
+/// For big coverage masks where an `Array2<bool>` would use a byte a cell.  Cells are
+/// addressed the same way as everywhere else, `x` the column and `y` the row, and are
+/// stored row by row.
+///
+/// # Examples
+///
+/// ```
+/// use geospatial::BitGrid;
+///
+/// let mut mask = BitGrid::new(100, 3);
+/// mask.set(70, 2, true);
+/// assert!(mask.get(70, 2));
+/// assert!(!mask.get(69, 2));
+/// assert_eq!(mask.count_ones(), 1);
+/// let a = mask.to_array();
+/// assert_eq!(a.dim(), (3, 100));
+/// assert!(a[[2, 70]]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitGrid {
+    bits: Vec<u64>,
+    width: usize,
+    height: usize,
+}
+
+impl BitGrid {
+    /// An all `false` grid `width` columns wide and `height` rows high
+    pub fn new(width: usize, height: usize) -> Self {
+        BitGrid {
+            bits: vec![0; (width * height).div_ceil(64)],
+            width,
+            height,
+        }
+    }
+
+    /// Number of columns
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Number of rows
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // word and bit for a cell
+    fn locate(&self, x: usize, y: usize) -> (usize, u64) {
+        assert!(
+            x < self.width && y < self.height,
+            "cell ({x}, {y}) is outside a {}x{} grid",
+            self.width,
+            self.height
+        );
+        let i = y * self.width + x;
+        (i / 64, 1 << (i % 64))
+    }
+
+    /// The value of the cell in column `x`, row `y`
+    ///
+    /// # Panics
+    ///
+    /// If the cell is outside the grid.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        let (w, b) = self.locate(x, y);
+        self.bits[w] & b != 0
+    }
+
+    /// Set the cell in column `x`, row `y`
+    ///
+    /// # Panics
+    ///
+    /// If the cell is outside the grid.
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        let (w, b) = self.locate(x, y);
+        if value {
+            self.bits[w] |= b;
+        } else {
+            self.bits[w] &= !b;
+        }
+    }
+
+    /// How many cells are `true`
+    pub fn count_ones(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Unpack into an `Array2<bool>` indexed `[[row, col]]`
+    pub fn to_array(&self) -> Array2<bool> {
+        Array2::from_shape_fn((self.height, self.width), |(r, c)| self.get(c, r))
+    }
+}
+
+impl From<&BitGrid> for Array2<bool> {
+    fn from(mask: &BitGrid) -> Self {
+        mask.to_array()
+    }
+}
+
+/// Rasterize a linestring straight into a grid, writing `value` into every cell it touches
+///
+/// The cells are those of [`rasterize_linestring`], with `Coord { x, y }` written to
+/// `grid[[y, x]]`.
+///
+/// # Notes
+///
+/// Cells outside the grid, including any negative ones, are skipped, so lines may run off
+/// its edges.
+///
+/// # Examples
+///
+/// ```
+/// use geo::LineString;
+/// use ndarray::{Array2, array};
+///
+/// let mut grid = Array2::<u8>::zeros((3, 3));
+/// let ls: LineString<i32> = LineString::from(vec![(-1, 0), (2, 0), (2, 5)]);
+/// geospatial::burn_linestring(&mut grid, &ls, 7);
+/// assert_eq!(grid, array![
+///     [7, 7, 7],
+///     [0, 0, 7],
+///     [0, 0, 7],
+/// ]);
+/// ```
+pub fn burn_linestring<T, V>(grid: &mut Array2<V>, ls: &LineString<T>, value: V)
+where
+    T: CoordNum + SignedNum,
+    V: Copy,
+{
+    for c in rasterize_linestring_iter(ls) {
+        if let (Some(x), Some(y)) = (c.x.to_usize(), c.y.to_usize())
+            && let Some(cell) = grid.get_mut([y, x])
+        {
+            *cell = value;
+        }
+    }
+}
+
+/// Rasterize a linestring into a [`BitGrid`]
+///
+/// Sets every cell [`rasterize_linestring`] gives for `ls`, so many lines can be
+/// accumulated into one mask without keeping their cells around.
+///
+/// # Notes
+///
+/// Cells outside the mask are ignored, so lines may run off its edges.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+/// use geospatial::BitGrid;
+///
+/// let mut mask = BitGrid::new(4, 4);
+/// let ls: LineString<isize> = LineString::new(vec![
+///     Coord { x: -1, y: 1 },
+///     Coord { x: 5, y: 1 },
+/// ]);
+/// geospatial::burn_linestring_bitset(&mut mask, &ls);
+/// assert_eq!(mask.count_ones(), 4);
+/// assert!((0..4).all(|x| mask.get(x, 1)));
+/// ```
+pub fn burn_linestring_bitset(mask: &mut BitGrid, ls: &LineString<isize>) {
+    for c in rasterize_linestring(ls) {
+        if c.x >= 0 && c.y >= 0 && (c.x as usize) < mask.width && (c.y as usize) < mask.height {
+            mask.set(c.x as usize, c.y as usize, true);
+        }
+    }
+}
+
+/// Burn lines into a grid, higher priority lines on top
+///
+/// Each line is `(line, value, priority)`.  Its cells, from [`rasterize_linestring`], are
+/// set to `value` unless a line of higher priority has already been burned there, so the
+/// order of `lines` only matters between lines of equal priority, where the last wins.
+///
+/// # Notes
+///
+/// - Cells no line touches are left as they were, and the values already in the grid lose
+///   to any line.
+/// - Cells outside the grid are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use geo::LineString;
+/// use ndarray::Array2;
+///
+/// let mut grid: Array2<u16> = Array2::zeros((3, 3));
+/// let road = LineString::from(vec![(0, 1), (2, 1)]);
+/// let track = LineString::from(vec![(1, 0), (1, 2)]);
+/// geospatial::burn_linestrings_priority(&mut grid, &[(road, 7, 2), (track, 3, 1)]);
+/// assert_eq!(grid, ndarray::array![[0, 3, 0], [7, 7, 7], [0, 3, 0]]);
+/// ```
+pub fn burn_linestrings_priority(grid: &mut Array2<u16>, lines: &[(LineString<isize>, u16, u8)]) {
+    let (nrows, ncols) = grid.dim();
+    let mut burned: Array2<Option<u8>> = Array2::from_elem((nrows, ncols), None);
+    for (ls, value, priority) in lines {
+        for c in rasterize_linestring(ls) {
+            if c.x < 0 || c.y < 0 || c.x as usize >= ncols || c.y as usize >= nrows {
+                continue;
+            }
+            let ix = [c.y as usize, c.x as usize];
+            if burned[ix].is_none_or(|p| *priority >= p) {
+                burned[ix] = Some(*priority);
+                grid[ix] = *value;
+            }
+        }
+    }
+}
+
+// cells whose centers are inside the polygon, even-odd over all its rings, row by row.
+// cell (x, y) has its center at (x + at, y + at).  closed takes centers on the left and
+// right ends of a span, otherwise only the left, and either way only the top of a span
+// of rows, as the crossings are half open in y
+fn fill_polygon<T>(poly: &geo::Polygon<T>, at: f64, closed: bool) -> Vec<Coord<T>>
+where
+    T: CoordNum,
+{
+    let to_f = |c: &Coord<T>| (c.x.to_f64().unwrap(), c.y.to_f64().unwrap());
+    let rings: Vec<&LineString<T>> = std::iter::once(poly.exterior())
+        .chain(poly.interiors())
+        .collect();
+    let ys = poly.exterior().0.iter().map(|c| to_f(c).1);
+    let (lo, hi) = ys.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), y| {
+        (lo.min(y), hi.max(y))
+    });
+    let mut out = Vec::new();
+    if lo > hi {
+        return out;
+    }
+    let mut xs = Vec::new();
+    for y in (lo - at).ceil() as i64..=(hi - at).floor() as i64 {
+        let yf = y as f64 + at;
+        xs.clear();
+        for ring in &rings {
+            for w in ring.0.windows(2) {
+                let ((x0, y0), (x1, y1)) = (to_f(&w[0]), to_f(&w[1]));
+                if (y0 <= yf) != (y1 <= yf) {
+                    xs.push(x0 + (yf - y0) * (x1 - x0) / (y1 - y0));
+                }
+            }
+        }
+        xs.sort_by(f64::total_cmp);
+        for pair in xs.chunks_exact(2) {
+            let (a, b) = (pair[0] - at, pair[1] - at);
+            let end = if closed {
+                b.floor() as i64 + 1
+            } else {
+                b.ceil() as i64
+            };
+            for x in a.ceil() as i64..end {
+                out.push(Coord {
+                    x: T::from(x).unwrap(),
+                    y: T::from(y).unwrap(),
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Fill a polygon, giving the cells whose centers are inside it
+///
+/// The polygon's coordinates are grid corners, as in the output of
+/// [`edges_to_multilinestring`] and [`region_to_multipolygon`], so cell `(x, y)` covers
+/// `x` to `x + 1` and `y` to `y + 1` with its center at `(x + 0.5, y + 0.5)`.  Filling a
+/// polygon from marching squares gives back exactly the cells of its region.  This is
+/// unlike [`rasterize_linestring`] and [`rasterize_geometry`], where coordinates are cell
+/// centers.
+///
+/// # Returns
+///
+/// The cells inside the exterior ring and not in any hole, by the even-odd rule, row by
+/// row from the top and left to right along each row.
+///
+/// # Notes
+///
+/// - A center exactly on an edge goes by the top-left rule: it is in the polygon if the
+///   edge is a left or top edge, and not if it is a right or bottom one.  So polygons
+///   sharing an edge never both claim a cell, and a tiling of polygons claims each cell
+///   once.
+/// - `y` is taken as increasing down the grid, as for rows, so top means smaller `y`.
+///
+/// # Panics
+///
+/// If a cell is outside the range of `T`, eg a polygon with negative coordinates for
+/// `T = usize`.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString, Polygon};
+///
+/// // a 3x3 square with the middle cell cut out
+/// let poly = Polygon::new(
+///     LineString::from(vec![(0, 0), (3, 0), (3, 3), (0, 3), (0, 0)]),
+///     vec![LineString::from(vec![(1, 1), (2, 1), (2, 2), (1, 2), (1, 1)])],
+/// );
+/// let cells = geospatial::rasterize_polygon(&poly);
+/// assert_eq!(cells.len(), 8);
+/// assert!(!cells.contains(&Coord { x: 1, y: 1 }));
+///
+/// // two triangles sharing a diagonal through cell centers claim each cell once
+/// let a = Polygon::new(LineString::from(vec![(0, 0), (2, 0), (0, 2), (0, 0)]), vec![]);
+/// let b = Polygon::new(LineString::from(vec![(2, 0), (2, 2), (0, 2), (2, 0)]), vec![]);
+/// let (ca, cb) = (geospatial::rasterize_polygon(&a), geospatial::rasterize_polygon(&b));
+/// assert_eq!(ca.len() + cb.len(), 4);
+/// assert!(ca.iter().all(|c| !cb.contains(c)));
+/// ```
+pub fn rasterize_polygon<T>(poly: &geo::Polygon<T>) -> Vec<Coord<T>>
+where
+    T: CoordNum,
+{
+    fill_polygon(poly, 0.5, false)
+}
+
+/// Rasterize every line of a `MultiLineString`
+///
+/// [`rasterize_linestring`] on each line in turn, with the results merged.
+///
+/// # Returns
+///
+/// Every cell touched by any of the lines, each once, in the order the lines first reach
+/// them.  A cell where lines cross or meet is only listed for the first.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString, MultiLineString};
+///
+/// // two lines crossing at (1, 1)
+/// let mls = MultiLineString::new(vec![
+///     LineString::from(vec![(0, 1), (2, 1)]),
+///     LineString::from(vec![(1, 0), (1, 2)]),
+/// ]);
+/// assert_eq!(
+///     geospatial::rasterize_multilinestring(&mls),
+///     vec![
+///         Coord { x: 0, y: 1 },
+///         Coord { x: 1, y: 1 },
+///         Coord { x: 2, y: 1 },
+///         Coord { x: 1, y: 0 },
+///         Coord { x: 1, y: 2 },
+///     ]
+/// );
+/// ```
+pub fn rasterize_multilinestring<T>(mls: &MultiLineString<T>) -> Vec<Coord<T>>
+where
+    T: CoordNum + SignedNum + Hash,
+{
+    let mut seen: LabelSet<Coord<T>> = LabelSet::default();
+    let mut out = Vec::new();
+    for ls in mls {
+        out.extend(
+            rasterize_linestring(ls)
+                .into_iter()
+                .filter(|c| seen.insert(*c)),
+        );
+    }
+    out
+}
+
+/// How many of the lines pass through each cell
+///
+/// Each line is rasterized with [`rasterize_linestring`] and adds 1 to every cell it
+/// touches, for density maps of tracks.
+///
+/// # Returns
+///
+/// The count for every cell touched by at least one line.
+///
+/// # Notes
+///
+/// - Counts are per line, not per visit.  Within a line each cell counts once, however
+///   many times the line comes back to it, so a track that doubles back still adds only 1.
+///   Across lines counts add up, so two lines through a cell give it 2.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let lines: Vec<LineString<i32>> = vec![
+///     // there and back again along row 0
+///     LineString::from(vec![(0, 0), (2, 0), (0, 0)]),
+///     // down column 1
+///     LineString::from(vec![(1, 0), (1, 2)]),
+/// ];
+/// let density = geospatial::rasterize_linestrings_density(&lines);
+/// assert_eq!(density[&Coord { x: 0, y: 0 }], 1);
+/// assert_eq!(density[&Coord { x: 1, y: 0 }], 2);
+/// assert_eq!(density[&Coord { x: 1, y: 2 }], 1);
+/// assert_eq!(density.len(), 5);
+/// ```
+pub fn rasterize_linestrings_density<T>(lines: &[LineString<T>]) -> HashMap<Coord<T>, u32>
+where
+    T: CoordNum + SignedNum + Hash,
+{
+    let mut density: HashMap<Coord<T>, u32> = HashMap::new();
+    let mut seen: LabelSet<Coord<T>> = LabelSet::default();
+    for ls in lines {
+        seen.clear();
+        for c in rasterize_linestring_iter(ls) {
+            if seen.insert(c) {
+                *density.entry(c).or_default() += 1;
+            }
+        }
+    }
+    density
+}
+
+/// Rasterize any `geo::Geometry`
+///
+/// One call for mixed data, handy when burning a whole layer into a raster.  Each variant
+/// is handled as you'd expect:
+///
+/// - a `Point` is its own cell, a `MultiPoint` each of its points
+/// - a `Line`, `LineString` or `MultiLineString` gives the cells from
+///   [`rasterize_linestring`]
+/// - a `Polygon`, `Rect` or `Triangle` is filled, giving the cells along all its rings
+///   and every cell whose center is inside it but not in a hole
+/// - `MultiPolygon` and `GeometryCollection` are their parts
+///
+/// # Returns
+///
+/// Every cell touched, each once, in the order the parts of `geom` first reach them.
+///
+/// # Notes
+///
+/// - Coordinates are cells as in [`rasterize_linestring`], so a vertex `(x, y)` is at
+///   the center of cell `(x, y)`.
+/// - Every variant is supported.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, Geometry, GeometryCollection, LineString, Point, Rect};
+///
+/// let g = Geometry::GeometryCollection(GeometryCollection::new_from(vec![
+///     Geometry::Point(Point::new(9, 9)),
+///     Geometry::LineString(LineString::from(vec![(0, 0), (2, 0)])),
+///     Geometry::Rect(Rect::new((0, 0), (2, 2))),
+/// ]));
+/// let cells = geospatial::rasterize_geometry(&g);
+/// assert_eq!(cells.len(), 1 + 9);
+/// assert_eq!(cells[0], Coord { x: 9, y: 9 });
+/// assert_eq!(cells[1..4], [Coord { x: 0, y: 0 }, Coord { x: 1, y: 0 }, Coord { x: 2, y: 0 }]);
+/// assert!(cells.contains(&Coord { x: 1, y: 1 }));
+/// ```
+pub fn rasterize_geometry<T>(geom: &geo::Geometry<T>) -> Vec<Coord<T>>
+where
+    T: CoordNum + SignedNum + Hash,
+{
+    fn burn<T>(geom: &geo::Geometry<T>, out: &mut Vec<Coord<T>>)
+    where
+        T: CoordNum + SignedNum,
+    {
+        use geo::Geometry as G;
+        let polygon = |p: &geo::Polygon<T>, out: &mut Vec<Coord<T>>| {
+            for ring in std::iter::once(p.exterior()).chain(p.interiors()) {
+                out.extend(rasterize_linestring(ring));
+            }
+            out.extend(fill_polygon(p, 0.0, true));
+        };
+        match geom {
+            G::Point(p) => out.push(p.0),
+            G::MultiPoint(mp) => out.extend(mp.iter().map(|p| p.0)),
+            G::Line(l) => out.extend(rasterize_linestring(&LineString::new(vec![l.start, l.end]))),
+            G::LineString(ls) => out.extend(rasterize_linestring(ls)),
+            G::MultiLineString(mls) => {
+                for ls in mls {
+                    out.extend(rasterize_linestring(ls));
+                }
+            }
+            G::Polygon(p) => polygon(p, out),
+            G::MultiPolygon(mp) => {
+                for p in mp {
+                    polygon(p, out);
+                }
+            }
+            G::Rect(r) => polygon(&r.to_polygon(), out),
+            G::Triangle(t) => polygon(&t.to_polygon(), out),
+            G::GeometryCollection(gc) => {
+                for g in gc {
+                    burn(g, out);
+                }
+            }
+        }
+    }
+
+    let mut all = Vec::new();
+    burn(geom, &mut all);
+    let mut seen: LabelSet<Coord<T>> = LabelSet::default();
+    all.retain(|c| seen.insert(*c));
+    all
+}
+
+/// Marching squares
+///
+/// Extracts boundary edges from a 2d array.  A horizontal or vertical edge exists between
+/// two cells if they have
+/// different values. It is intended for
+/// grids containing region or watershed labels, where each distinct value represents
+/// a separate area and you want to get the boundary edges.
+///
+/// # Parameters
+///
+/// - `grid`: A 2D array of values representing labeled regions.  Anything that converts to
+///   an `ArrayView2` works, `&Array2` or a view such as a slice of a bigger grid, so tiles
+///   don't need copying out first.
+///
+/// # Returns
+///
+/// A HashMap mapping each unique grid value to a list of edges `(Coord<usize>, Coord<usize>)`
+/// associated with that region.
+///
+/// # Notes
+///
+/// - Interior cells that are completely surrounded by the same value won't generate an edge.
+/// - This function does **not** return full polygon boundaries; it only identifies
+///   boundary edges that will need to be assembled into a polygon
+/// - The grid is scanned twice, once to count each label's edges and once to fill them, so
+///   every `Vec` is allocated exactly once at its final size.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::{array, Array2};
+/// use std::collections::HashMap;
+/// use geo::{Coord};
+/// use std::hash::Hash;
+///
+/// let grid = array![
+///     [1],
+/// ];
+/// assert_eq!(geospatial::marching_squares(&grid)[&1],
+///     vec![
+///         (Coord{ x: 0, y: 0}, Coord{ x: 1, y: 0}),
+///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
+///         (Coord{ x: 0, y: 0}, Coord{ x: 0, y: 1}),
+///         (Coord{ x: 1, y: 0}, Coord{ x: 1, y: 1}),
+///     ]
+/// );
+///
+/// let grid = array![
+///     [1, 1],
+/// ];
+/// assert_eq!(geospatial::marching_squares(&grid)[&1],
+///     vec![
+///         (Coord{ x: 0, y: 0}, Coord{ x: 1, y: 0}),
+///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
+///         (Coord{ x: 1, y: 0}, Coord{ x: 2, y: 0}),
+///         (Coord{ x: 1, y: 1}, Coord{ x: 2, y: 1}),
+///         (Coord{ x: 0, y: 0}, Coord{ x: 0, y: 1}),
+///         (Coord{ x: 2, y: 0}, Coord{ x: 2, y: 1}),
+///     ]
+/// );
+///
+/// let grid = array![
+///     [1, 1],
+///     [2, 1],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// assert_eq!(e[&1],
+///     vec![
+///         (Coord{ x: 0, y: 0}, Coord{ x: 1, y: 0}),
+///         (Coord{ x: 1, y: 0}, Coord{ x: 2, y: 0}),
+///         (Coord{ x: 1, y: 2}, Coord{ x: 2, y: 2}),
+///         (Coord{ x: 0, y: 0}, Coord{ x: 0, y: 1}),
+///         (Coord{ x: 2, y: 0}, Coord{ x: 2, y: 1}),
+///         (Coord{ x: 2, y: 1}, Coord{ x: 2, y: 2}),
+///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
+///         (Coord{ x: 1, y: 1}, Coord{ x: 1, y: 2}),
+///     ]
+/// );
+/// assert_eq!(e[&2],
+///     vec![
+///         (Coord{ x: 0, y: 2}, Coord{ x: 1, y: 2}),
+///         (Coord{ x: 0, y: 1}, Coord{ x: 0, y: 2}),
+///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
+///         (Coord{ x: 1, y: 1}, Coord{ x: 1, y: 2}),
+///     ]
+/// );
+/// let grid = array![
+///     [4, 1, 1, 2],
+///     [1, 1, 2, 3],
+///     [1, 2, 2, 2],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// assert_eq!(e[&4],
+///     vec![
+///         (Coord{ x: 0, y: 0}, Coord{ x: 1, y: 0}),
+///         (Coord{ x: 0, y: 0}, Coord{ x: 0, y: 1}),
+///         (Coord{ x: 1, y: 0}, Coord{ x: 1, y: 1}),
+///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
+///     ]
+/// );
+/// assert_eq!(e[&3],
+///     vec![
+///         (Coord{ x: 4, y: 1}, Coord{ x: 4, y: 2}),
+///         (Coord{ x: 3, y: 1}, Coord{ x: 3, y: 2}),
+///         (Coord{ x: 3, y: 1}, Coord{ x: 4, y: 1}),
+///         (Coord{ x: 3, y: 2}, Coord{ x: 4, y: 2}),
+///     ]
+/// );
+/// assert_eq!(e[&2],
+///     vec![
+///         (Coord{ x: 1, y: 3}, Coord{ x: 2, y: 3}),
+///         (Coord{ x: 2, y: 3}, Coord{ x: 3, y: 3}),
+///         (Coord{ x: 3, y: 0}, Coord{ x: 4, y: 0}),
+///         (Coord{ x: 3, y: 3}, Coord{ x: 4, y: 3}),
+///         (Coord{ x: 4, y: 0}, Coord{ x: 4, y: 1}),
+///         (Coord{ x: 4, y: 2}, Coord{ x: 4, y: 3}),
+///         (Coord{ x: 3, y: 0}, Coord{ x: 3, y: 1}),
+///         (Coord{ x: 2, y: 1}, Coord{ x: 3, y: 1}),
+///         (Coord{ x: 2, y: 1}, Coord{ x: 2, y: 2}),
+///         (Coord{ x: 1, y: 2}, Coord{ x: 2, y: 2}),
+///         (Coord{ x: 3, y: 1}, Coord{ x: 3, y: 2}),
+///         (Coord{ x: 3, y: 1}, Coord{ x: 4, y: 1}),
+///         (Coord{ x: 3, y: 2}, Coord{ x: 4, y: 2}),
+///         (Coord{ x: 1, y: 2}, Coord{ x: 1, y: 3}),
+///     ]
+/// );
+/// assert_eq!(e[&1],
+///     vec![
+///         (Coord{ x: 0, y: 3}, Coord{ x: 1, y: 3}),
+///         (Coord{ x: 1, y: 0}, Coord{ x: 2, y: 0}),
+///         (Coord{ x: 2, y: 0}, Coord{ x: 3, y: 0}),
+///         (Coord{ x: 0, y: 1}, Coord{ x: 0, y: 2}),
+///         (Coord{ x: 0, y: 2}, Coord{ x: 0, y: 3}),
+///         (Coord{ x: 1, y: 0}, Coord{ x: 1, y: 1}),
+///         (Coord{ x: 0, y: 1}, Coord{ x: 1, y: 1}),
+///         (Coord{ x: 3, y: 0}, Coord{ x: 3, y: 1}),
+///         (Coord{ x: 2, y: 1}, Coord{ x: 3, y: 1}),
+///         (Coord{ x: 2, y: 1}, Coord{ x: 2, y: 2}),
+///         (Coord{ x: 1, y: 2}, Coord{ x: 2, y: 2}),
+///         (Coord{ x: 1, y: 2}, Coord{ x: 1, y: 3}),
+///     ]
+/// );
+///
+/// // a view of part of the grid, without copying it
+/// let tile = geospatial::marching_squares(grid.slice(ndarray::s![0..2, 1..3]));
+/// assert_eq!(tile, geospatial::marching_squares(&array![[1, 1], [1, 2]]));
+///
+/// // a single row or column only has edges around the border, and an empty grid none
+/// let row = geospatial::marching_squares(&array![[1, 1, 2]]);
+/// assert_eq!(row[&1].len(), 6);
+/// assert_eq!(row[&2], vec![
+///     (Coord{ x: 2, y: 0}, Coord{ x: 3, y: 0}),
+///     (Coord{ x: 2, y: 1}, Coord{ x: 3, y: 1}),
+///     (Coord{ x: 3, y: 0}, Coord{ x: 3, y: 1}),
+///     (Coord{ x: 2, y: 0}, Coord{ x: 2, y: 1}),
+/// ]);
+/// let col = geospatial::marching_squares(&array![[1], [1], [2]]);
+/// assert_eq!(col[&1].len(), 6);
+/// assert_eq!(col[&2].len(), 4);
+/// assert!(geospatial::marching_squares(&ndarray::Array2::<i32>::zeros((0, 3))).is_empty());
+/// assert!(geospatial::marching_squares(&ndarray::Array2::<i32>::zeros((0, 0))).is_empty());
+///
+/// // labels only need to be Clone, so they can be names
+/// let names = array![
+///     ["Amazon".to_string(), "Congo".to_string()],
+///     ["Amazon".to_string(), "Amazon".to_string()],
+/// ];
+/// let e = geospatial::marching_squares(&names);
+/// assert_eq!(e["Congo"].len(), 4);
+/// assert_eq!(e["Amazon"].len(), 8);
+/// ```
+pub fn marching_squares<'a, T>(grid: impl Into<ArrayView2<'a, T>>) -> LabelMap<T, Vec<GridEdge>>
+where
+    T: Eq + Hash + Clone + 'a,
+{
+    marching_squares_connectivity(grid, Connectivity::Four)
+}
+
+/// [`marching_squares`], optionally joining regions through corners they touch diagonally
+///
+/// With [`Connectivity::Four`] this is exactly [`marching_squares`], cells that only
+/// touch at a corner are separate and their boundaries meet there in a knot.  With
+/// [`Connectivity::Eight`] diagonal neighbours with the same label are joined: at each
+/// knot where one diagonal pair of cells has the same label, the two other cells each give
+/// up the triangle nearest the knot, and the boundary cuts diagonally across them.  So a
+/// diagonal staircase of cells comes out as one band with straight diagonal sides, and
+/// the edges can still be assembled into rings by [`edges_to_multilinestring`].
+///
+/// # Notes
+///
+/// - Diagonal edges run between opposite corners of a cell, and are listed after the
+///   unit edges.
+/// - Where both diagonal pairs at a knot have the same label, as in a checkerboard, the
+///   pair from top left to bottom right is joined.
+/// - A cell is cut at most once, and a cell that has been cut can't join a pair at another
+///   knot.  Where two knots need the same cell, only the first, row by row, is joined and
+///   the other is left as with `Four`, so the middle of an X of cells stays apart from
+///   some of its arms.
+/// - Knots are found with nodata cells counted as a label, but a nodata pair is never
+///   joined.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use geospatial::Connectivity;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 0],
+///     [0, 1],
+/// ];
+///
+/// // with four connectivity the 1s are two squares touching at the middle
+/// let four = geospatial::marching_squares_connectivity(&grid, Connectivity::Four);
+/// assert_eq!(four[&1].len(), 8);
+/// assert_eq!(geospatial::edges_to_multilinestring(1, &four[&1], &grid).0.len(), 2);
+///
+/// // with eight they are one band from corner to corner, and each 0 is a triangle
+/// let eight = geospatial::marching_squares_connectivity(&grid, Connectivity::Eight);
+/// assert_eq!(eight[&1].len(), 6);
+/// assert!(eight[&1].contains(&(Coord { x: 1, y: 0 }, Coord { x: 2, y: 1 })));
+/// assert!(eight[&1].contains(&(Coord { x: 0, y: 1 }, Coord { x: 1, y: 2 })));
+/// let mls = geospatial::edges_to_multilinestring(1, &eight[&1], &grid);
+/// assert_eq!(mls.0.len(), 1);
+/// assert_eq!(geospatial::polygon_area(&mls.0[0]).abs(), 3);
+/// assert_eq!(geospatial::edges_to_multilinestring(0, &eight[&0], &grid).0.len(), 2);
+/// ```
+pub fn marching_squares_connectivity<'a, T>(
+    grid: impl Into<ArrayView2<'a, T>>,
+    connectivity: Connectivity,
+) -> LabelMap<T, Vec<GridEdge>>
+where
+    T: Eq + Hash + Clone + 'a,
+{
+    MarchingSquares::new().connectivity(connectivity).run(grid)
+}
+
+/// [`marching_squares`] into a map kept from an earlier call, to reuse its allocations
+///
+/// For tracing many small grids in a loop.  The edge `Vec`s already in `out` are cleared
+/// and refilled, keeping their capacity, so once the map has seen a grid's labels tracing
+/// another like it allocates nothing.
+///
+/// # Returns
+///
+/// `out` holds the same edges as [`marching_squares`] would give, in the same order.
+/// Labels from before that aren't in `grid` are removed, and their `Vec`s freed.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let mut out = geospatial::LabelMap::default();
+/// let grids = [array![[1, 1], [1, 2]], array![[2, 1], [1, 1]], array![[3, 3], [3, 3]]];
+/// for grid in &grids {
+///     geospatial::marching_squares_into(grid, &mut out);
+///     assert_eq!(out, geospatial::marching_squares(grid));
+/// }
+/// ```
+pub fn marching_squares_into<'a, T>(
+    grid: impl Into<ArrayView2<'a, T>>,
+    out: &mut LabelMap<T, Vec<GridEdge>>,
+) where
+    T: Eq + Hash + Clone + 'a,
+{
+    let view: ArrayView2<T> = grid.into();
+    for v in out.values_mut() {
+        v.clear();
+    }
+    marching_squares_scan(&view, |label, edge| {
+        if let Some(v) = out.get_mut(label) {
+            v.push(edge);
+        } else {
+            out.insert(label.clone(), vec![edge]);
+        }
+    });
+    out.retain(|_, v| !v.is_empty());
+}
+
+/// [`marching_squares`] with the `nodata` cells left out
+///
+/// Cells with the value `nodata` aren't part of any region.  They get no edges, so there is
+/// never a `nodata` key and no polygon for them, and that includes the grid border next to
+/// them, which has no edge either.  The same as [`MarchingSquares::nodata`].
+///
+/// # Notes
+///
+/// - The regions next to a nodata cell still have an edge against it, their boundary
+///   stops there just as it does at the grid border.  Dropping those edges too would leave
+///   the regions' rings open, and [`edges_to_multilinestring`] couldn't close them.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [-1, -1, 2],
+///     [ 1,  1, 2],
+/// ];
+/// let e = geospatial::marching_squares_masked(&grid, -1);
+/// assert_eq!(e.len(), 2);
+/// assert!(!e.contains_key(&-1));
+/// // a 2x1 rectangle, closed off along the nodata cells above it
+/// assert_eq!(e[&1].len(), 6);
+/// let mls = geospatial::edges_to_multilinestring(1, &e[&1], &grid);
+/// assert_eq!(geospatial::polygon_area(&mls.0[0]), 2);
+/// ```
+pub fn marching_squares_masked<'a, T>(
+    grid: impl Into<ArrayView2<'a, T>>,
+    nodata: T,
+) -> LabelMap<T, Vec<GridEdge>>
+where
+    T: Eq + Hash + Clone + 'a,
+{
+    MarchingSquares::new().nodata(nodata).run(grid)
+}
+
+/// The outline of everything that isn't `background`, as one region
+///
+/// Every cell other than `background` counts as data, whatever its label, and edges go
+/// where data meets background or the grid border.  This is [`marching_squares`] of the
+/// data mask, `grid.map(|v| *v != background)`, so the edges assemble with
+/// [`edges_to_multilinestring`] using that mask and the id `true`, and background inside
+/// the data comes out as holes.
+///
+/// # Returns
+///
+/// The footprint's edges, empty if every cell is background.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 2, 2],
+///     [1, 0, 0, 2],
+///     [3, 3, 3, 2],
+/// ];
+/// let edges = geospatial::footprint_boundary(&grid, 0);
+/// assert_eq!(edges.len(), 14 + 6);
+/// let mask = grid.map(|v| *v != 0);
+/// let mls = geospatial::edges_to_multilinestring(true, &edges, &mask);
+/// // the outside of the grid, and a hole where the 0s are
+/// assert_eq!(mls.0.len(), 2);
+/// ```
+pub fn footprint_boundary<T>(grid: &Array2<T>, background: T) -> Vec<GridEdge>
+where
+    T: Eq + Copy,
+{
+    marching_squares(&grid.map(|v| *v != background))
+        .remove(&true)
+        .unwrap_or_default()
+}
+
+/// [`marching_squares`] for only some of the labels
+///
+/// Every cell is still compared with its neighbours, but edges are only kept for the labels
+/// in `wanted`, so picking a few regions out of hundreds doesn't build edge lists for the
+/// rest.  Each label's edges are exactly those [`marching_squares`] gives it, in the same
+/// order.
+///
+/// # Returns
+///
+/// A map whose keys are the labels of `wanted` that are in the grid.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+/// use std::collections::HashSet;
+///
+/// let grid = array![
+///     [1, 1, 2],
+///     [3, 1, 2],
+/// ];
+/// let wanted = HashSet::from([2, 7]);
+/// let e = geospatial::marching_squares_subset(&grid, &wanted);
+/// assert_eq!(e.len(), 1);
+/// assert_eq!(e[&2], geospatial::marching_squares(&grid)[&2]);
+/// ```
+pub fn marching_squares_subset<T, S>(
+    grid: &Array2<T>,
+    wanted: &HashSet<T, S>,
+) -> LabelMap<T, Vec<GridEdge>>
+where
+    T: Eq + Hash + Copy,
+    S: std::hash::BuildHasher,
+{
+    let mut ret: LabelMap<T, Vec<GridEdge>> = LabelMap::default();
+    let view = grid.view();
+
+    // count first, as in MarchingSquares::run, so each vec is allocated once
+    let mut counts: LabelMap<T, usize> = LabelMap::default();
+    marching_squares_scan(&view, |label, _| {
+        if wanted.contains(label) {
+            *counts.entry(*label).or_default() += 1;
+        }
+    });
+    ret.reserve(counts.len());
+    for (label, n) in counts {
+        ret.insert(label, Vec::with_capacity(n));
+    }
+    marching_squares_scan(&view, |label, edge| {
+        if let Some(v) = ret.get_mut(label) {
+            v.push(edge);
+        }
+    });
+    ret
+}
+
+/// Which sides of a tile are the edge of the whole raster, for [`marching_squares_tile`]
+///
+/// A side that is `true` gets edges all along it, as every side does in
+/// [`marching_squares`].  A side that is `false` is a seam with the neighbouring tile, and
+/// the tile's view must take in one row or column of that neighbour beyond it.  The
+/// default is every side `true`, a tile that is the whole raster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Borders {
+    /// the first row is the raster's top edge
+    pub top: bool,
+    /// the last row is the raster's bottom edge
+    pub bottom: bool,
+    /// the first column is the raster's left edge
+    pub left: bool,
+    /// the last column is the raster's right edge
+    pub right: bool,
+}
+
+impl Default for Borders {
+    fn default() -> Self {
+        Borders {
+            top: true,
+            bottom: true,
+            left: true,
+            right: true,
+        }
+    }
+}
+
+/// [`marching_squares`] on one tile of a raster too big to hold at once
+///
+/// A tile on its own can't tell whether a region carries on past its edge, so every tile
+/// but those at the raster's edge would get spurious edges all along its seams.  Instead
+/// each tile is given as a view reaching one cell into its neighbour across every seam,
+/// and `borders` says which sides are seams.  The row or column beyond a seam is only
+/// looked at: it gets no edges of its own, but the cells next to it get their edges
+/// against it, so the edges across the seam come out exactly once for each label either
+/// side, with the other label coming from the neighbouring tile.
+///
+/// # Parameters
+///
+/// - `grid`: The tile, with the extra row or column on each seam side.  Pass a slice of a
+///   bigger array, or a tile read from disk, without copying.
+/// - `borders`: Which sides are the raster's edge, `false` for a seam.
+///
+/// # Returns
+///
+/// Each label's edges, in the coordinates of the view, extra rows and columns included.
+/// Shift every tile's edges by where its view starts in the raster and the tiles' edges
+/// together are exactly those [`marching_squares`] gives the whole raster, but in a
+/// different order.  A view with nothing but seam rows or columns gives nothing.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use geospatial::Borders;
+/// use ndarray::{array, s};
+///
+/// let grid = array![
+///     [1, 1, 2],
+///     [1, 2, 2],
+///     [1, 1, 1],
+///     [3, 1, 1],
+/// ];
+/// // rows 0 and 1 in one tile, 2 and 3 in the other, each seeing one row of the other
+/// let top = geospatial::marching_squares_tile(
+///     &grid.slice(s![0..3, ..]),
+///     Borders { bottom: false, ..Borders::default() },
+/// );
+/// let bottom = geospatial::marching_squares_tile(
+///     &grid.slice(s![1..4, ..]),
+///     Borders { top: false, ..Borders::default() },
+/// );
+///
+/// let whole = geospatial::marching_squares(&grid);
+/// for (label, edges) in &whole {
+///     let mut tiled = top.get(label).cloned().unwrap_or_default();
+///     let shift = Coord { x: 0, y: 1 };
+///     for (a, b) in bottom.get(label).into_iter().flatten() {
+///         tiled.push((*a + shift, *b + shift));
+///     }
+///     let mut edges = edges.clone();
+///     tiled.sort_by_key(|(a, b)| (a.x, a.y, b.x, b.y));
+///     edges.sort_by_key(|(a, b)| (a.x, a.y, b.x, b.y));
+///     assert_eq!(tiled, edges);
+/// }
+/// ```
+pub fn marching_squares_tile<T>(
+    grid: &ArrayView2<T>,
+    borders: Borders,
+) -> LabelMap<T, Vec<GridEdge>>
+where
+    T: Eq + Hash + Copy,
+{
+    let (nrows, ncols) = grid.dim();
+    let mut ret: LabelMap<T, Vec<GridEdge>> = LabelMap::default();
+    // the rows and columns that are the tile's own, rather than its neighbours'
+    let r0 = usize::from(!borders.top);
+    let r1 = nrows.saturating_sub(usize::from(!borders.bottom));
+    let c0 = usize::from(!borders.left);
+    let c1 = ncols.saturating_sub(usize::from(!borders.right));
+
+    // each cell gives its own label the edges where it differs from the cell beyond, or
+    // where it is on the raster's edge
+    for r in r0..r1 {
+        for c in c0..c1 {
+            let me = grid[[r, c]];
+            let differs = |rr: Option<usize>, cc: Option<usize>| match (rr, cc) {
+                (Some(rr), Some(cc)) if rr < nrows && cc < ncols => grid[[rr, cc]] != me,
+                _ => true,
+            };
+            let (tl, tr) = (Coord { x: c, y: r }, Coord { x: c + 1, y: r });
+            let (bl, br) = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
+            let sides = [
+                ((tl, tr), r.checked_sub(1), Some(c)),
+                ((bl, br), Some(r + 1), Some(c)),
+                ((tl, bl), Some(r), c.checked_sub(1)),
+                ((tr, br), Some(r), Some(c + 1)),
+            ];
+            for (edge, rr, cc) in sides {
+                if differs(rr, cc) {
+                    ret.entry(me).or_default().push(edge);
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// Every boundary edge of a grid, with a custom test for whether two cells are the same
+/// region
+///
+/// An edge goes between neighbouring cells `a` and `b` wherever `!same(a, b)`, so values
+/// can be grouped without binning the grid first, eg elevations in the same 100m band.  A
+/// predicate like that needn't split the grid into labelled regions, so the edges come back
+/// in one list rather than by label.
+///
+/// # Returns
+///
+/// Each edge once: first those around the grid border, which is always a boundary as with
+/// [`marching_squares`], then the interior edges row by row.  With `same` as `==` the edges
+/// are the union of everything [`marching_squares`] gives.
+///
+/// # Notes
+///
+/// - If `same` isn't transitive a boundary can stop part way across the grid, so the edges
+///   needn't join up into rings.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [105.0, 160.0, 240.0],
+/// ];
+/// let e = geospatial::marching_squares_by(&grid, |a: &f64, b: &f64| {
+///     (a / 100.0).floor() == (b / 100.0).floor()
+/// });
+/// // 8 around the border, and one between the 100s and the 200s
+/// assert_eq!(e.len(), 9);
+/// assert_eq!(e[8], (Coord { x: 2, y: 0 }, Coord { x: 2, y: 1 }));
+/// ```
+pub fn marching_squares_by<T, F>(grid: &Array2<T>, same: F) -> Vec<GridEdge>
+where
+    F: Fn(&T, &T) -> bool,
+{
+    let (nrows, ncols) = grid.dim();
+    let mut ret = Vec::new();
+    if grid.is_empty() {
+        return ret;
+    }
+    for c in 0..ncols {
+        ret.push((Coord { x: c, y: 0 }, Coord { x: c + 1, y: 0 }));
+        ret.push((Coord { x: c, y: nrows }, Coord { x: c + 1, y: nrows }));
+    }
+    for r in 0..nrows {
+        ret.push((Coord { x: 0, y: r }, Coord { x: 0, y: r + 1 }));
+        ret.push((Coord { x: ncols, y: r }, Coord { x: ncols, y: r + 1 }));
+    }
+    for r in 0..nrows {
+        for c in 0..ncols {
+            let me = &grid[[r, c]];
+            if c + 1 < ncols && !same(me, &grid[[r, c + 1]]) {
+                ret.push((Coord { x: c + 1, y: r }, Coord { x: c + 1, y: r + 1 }));
+            }
+            if r + 1 < nrows && !same(me, &grid[[r + 1, c]]) {
+                ret.push((Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 }));
+            }
+        }
+    }
+    ret
+}
+
+/// Every boundary edge of a grid once, whichever labels it is between
+///
+/// [`marching_squares`] lists an edge between two regions under both of them, which is
+/// what [`edges_to_multilinestring`] needs, but drawing every region's outline from it
+/// strokes each shared border twice.  This gives each edge of the grid's boundaries just
+/// once, the same set as [`marching_squares_by`] with `==`.
+///
+/// # Returns
+///
+/// The edges row by row down the grid: the horizontal edges along the top of each row of
+/// cells from left to right, then the vertical edges across the row from left to right,
+/// and last the edges along the bottom of the grid.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 2],
+///     [1, 1],
+/// ];
+/// let edges = geospatial::all_boundary_edges(&grid);
+/// // 8 around the grid and 2 around the 2
+/// assert_eq!(edges.len(), 10);
+/// assert_eq!(edges[2], (Coord { x: 0, y: 0 }, Coord { x: 0, y: 1 }));
+/// let twice: usize = geospatial::marching_squares(&grid).values().map(|v| v.len()).sum();
+/// assert_eq!(twice, 12);
+/// ```
+pub fn all_boundary_edges<T>(grid: &Array2<T>) -> Vec<GridEdge>
+where
+    T: Eq,
+{
+    let (nrows, ncols) = grid.dim();
+    let mut ret = Vec::new();
+    if grid.is_empty() {
+        return ret;
+    }
+    for r in 0..=nrows {
+        for c in 0..ncols {
+            if r == 0 || r == nrows || grid[[r - 1, c]] != grid[[r, c]] {
+                ret.push((Coord { x: c, y: r }, Coord { x: c + 1, y: r }));
+            }
+        }
+        if r == nrows {
+            break;
+        }
+        for c in 0..=ncols {
+            if c == 0 || c == ncols || grid[[r, c - 1]] != grid[[r, c]] {
+                ret.push((Coord { x: c, y: r }, Coord { x: c, y: r + 1 }));
+            }
+        }
+    }
+    ret
+}
+
+/// [`marching_squares`] on grids whose values can't be hashed, like floats
+///
+/// Each cell's region is `key(value)`, and edges go between cells with different keys.
+/// Float category codes can use `f64::to_bits` as the key, and get the code back with
+/// `f64::from_bits`.
+///
+/// # Notes
+///
+/// - With `to_bits` as the key `0.0` and `-0.0` are different regions, and so are `NaN`s
+///   with different payloads.  Map those to one value first if that matters.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1.0, 1.0, 2.5],
+///     [1.0, 2.5, 2.5],
+/// ];
+/// let e = geospatial::marching_squares_by_key(&grid, |v: &f64| v.to_bits());
+/// assert_eq!(e.len(), 2);
+/// assert_eq!(e[&2.5f64.to_bits()], geospatial::marching_squares(&array![
+///     [0, 0, 1],
+///     [0, 1, 1],
+/// ])[&1]);
+/// ```
+pub fn marching_squares_by_key<T, K, F>(grid: &Array2<T>, key: F) -> LabelMap<K, Vec<GridEdge>>
+where
+    K: Eq + Hash + Copy,
+    F: Fn(&T) -> K,
+{
+    marching_squares(&grid.map(key))
+}
+
+/// Patches the output of [`marching_squares`] after a single cell changes value
+///
+/// Only the four edges around `cell` can change, so instead of scanning the whole grid
+/// again each one is removed from, or added to, the labels either side.  The value of
+/// `cell` in `grid` is ignored, only its neighbours are looked at, so this can be called
+/// before or after writing `new` into the grid.
+///
+/// # Parameters
+///
+/// - `edges`: The edges of `grid` with `old` at `cell`, as from [`marching_squares`].
+/// - `grid`: The grid, only read around `cell`.
+/// - `cell`: The changed cell, as `Coord { x: col, y: row }`.
+/// - `old`, `new`: Its value before and after.
+///
+/// # Notes
+///
+/// - Removing an edge searches its label's `Vec`, so the cost is the perimeter of the
+///   regions involved rather than the whole grid.
+/// - Edges are removed with `swap_remove` and added at the end, so afterwards the edges are
+///   the same set as [`marching_squares`] would give but in a different order.
+/// - A label left with no edges (its last cell was painted over) is removed from the map.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// let mut grid = array![
+///     [1, 1],
+///     [1, 2],
+/// ];
+/// let mut e = geospatial::marching_squares(&grid);
+/// grid[[0, 1]] = 2;
+/// geospatial::update_edges_for_cell(&mut e, &grid, Coord { x: 1, y: 0 }, 1, 2);
+/// let mut fresh = geospatial::marching_squares(&grid);
+/// for v in e.values_mut().chain(fresh.values_mut()) {
+///     v.sort_by_key(|(a, b)| (a.x, a.y, b.x, b.y));
+/// }
+/// assert_eq!(e, fresh);
+/// ```
+pub fn update_edges_for_cell<T>(
+    edges: &mut LabelMap<T, Vec<GridEdge>>,
+    grid: &Array2<T>,
+    cell: Coord<usize>,
+    old: T,
+    new: T,
+) where
+    T: Eq + Hash + Copy,
+{
+    if old == new {
+        return;
+    }
+    let (nrows, ncols) = grid.dim();
+    let (r, c) = (cell.y, cell.x);
+    let (tl, tr) = (Coord { x: c, y: r }, Coord { x: c + 1, y: r });
+    let (bl, br) = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
+    let sides = [
+        ((tl, tr), (r > 0).then(|| grid[[r - 1, c]])),
+        ((bl, br), (r + 1 < nrows).then(|| grid[[r + 1, c]])),
+        ((tl, bl), (c > 0).then(|| grid[[r, c - 1]])),
+        ((tr, br), (c + 1 < ncols).then(|| grid[[r, c + 1]])),
+    ];
+
+    let remove = |edges: &mut LabelMap<T, Vec<GridEdge>>, label: T, edge: GridEdge| {
+        if let Some(v) = edges.get_mut(&label) {
+            if let Some(k) = v.iter().position(|e| *e == edge) {
+                v.swap_remove(k);
+            }
+            if v.is_empty() {
+                edges.remove(&label);
+            }
+        }
+    };
+    for (edge, other) in sides {
+        // outside the grid always differs, so the border edge just changes hands
+        if other != Some(old) {
+            remove(edges, old, edge);
+            if let Some(o) = other {
+                remove(edges, o, edge);
+            }
+        }
+        if other != Some(new) {
+            edges.entry(new).or_default().push(edge);
+            if let Some(o) = other {
+                edges.entry(o).or_default().push(edge);
+            }
+        }
+    }
+}
+
+// walks every boundary edge in the order marching_squares emits them, handing each
+// (label, edge) pair to `emit`.  shared so we can count first and then fill
+fn marching_squares_scan<T, F>(grid: &ArrayView2<T>, mut emit: F)
+where
+    T: Eq,
+    F: FnMut(&T, GridEdge),
+{
+    let (nrows, ncols) = grid.dim();
+    if nrows == 0 || ncols == 0 {
+        return;
+    }
+
+    // we need edges around the entire grid, process top/bot row and left/right col at same time
+    for c in 0..ncols {
+        let r = 0;
+        let me = &grid[[r, c]];
+        let edge = (Coord { x: c, y: r }, Coord { x: c + 1, y: r });
+        emit(me, edge);
+        let r = nrows - 1;
+        let me = &grid[[r, c]];
+        let edge = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
+        emit(me, edge);
+    }
+    for r in 0..nrows {
+        let c = 0;
+        let me = &grid[[r, c]];
+        let edge = (Coord { x: c, y: r }, Coord { x: c, y: r + 1 });
+        emit(me, edge);
+        let c = ncols - 1;
+        let me = &grid[[r, c]];
+        let edge = (Coord { x: c + 1, y: r }, Coord { x: c + 1, y: r + 1 });
+        emit(me, edge);
+    }
+
+    // fill in the interior
+    for r in 0..nrows - 1 {
+        for c in 0..ncols - 1 {
+            let me = &grid[[r, c]];
+            let right = &grid[[r, c + 1]];
+            let down = &grid[[r + 1, c]];
+            if me != right {
+                let edge = (Coord { x: c + 1, y: r }, Coord { x: c + 1, y: r + 1 });
+                emit(me, edge);
+                emit(right, edge);
+            }
+            if me != down {
+                let edge = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
+                emit(me, edge);
+                emit(down, edge);
+            }
+        }
+    }
+
+    // last column, except bottom right hand cell
+    for r in 0..nrows - 1 {
+        let c = ncols - 1;
+        let me = &grid[[r, c]];
+        let down = &grid[[r + 1, c]];
+        if me != down {
+            let edge = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
+            emit(me, edge);
+            emit(down, edge);
+        }
+    }
+
+    // last row, except bottom right hand cell
+    for c in 0..ncols - 1 {
+        let r = nrows - 1;
+        let me = &grid[[r, c]];
+        let right = &grid[[r, c + 1]];
+        if me != right {
+            let edge = (Coord { x: c + 1, y: r }, Coord { x: c + 1, y: r + 1 });
+            emit(me, edge);
+            emit(right, edge);
+        }
+    }
+}
+
+/// The graph of a set of edges, each corner mapped to the corners it has an edge to
+///
+/// This is the graph [`edges_to_multilinestring`] walks to make rings.  Along a boundary
+/// every corner has two neighbours, and where a region touches itself diagonally there is
+/// a knot with four.  Any other degree means the
+/// edges don't form closed rings, see [`EdgeAssemblyError::DanglingVertex`].
+///
+/// # Returns
+///
+/// The neighbours of each corner in the order of the edges, with an edge listed twice
+/// giving a repeated neighbour.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 0],
+///     [0, 1],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let adj = geospatial::build_adjacency(&e[&1]);
+/// assert_eq!(adj.len(), 7);
+/// let knot = Coord { x: 1, y: 1 };
+/// assert_eq!(adj[&knot].len(), 4);
+/// assert!(adj.values().filter(|n| n.len() == 2).count() == 6);
+/// ```
+pub fn build_adjacency(edges: &[GridEdge]) -> LabelMap<Coord<usize>, Vec<Coord<usize>>> {
+    let mut adj: LabelMap<Coord<usize>, Vec<Coord<usize>>> = LabelMap::default();
+    for (a, b) in edges {
+        adj.entry(*a).or_default().push(*b);
+        adj.entry(*b).or_default().push(*a);
+    }
+    adj
+}
+
+/// Converts a collection of unordered grid edges that form a bunch of rings nto a
+/// `MultiLineString`.
+///
+/// This function takes a list of edges, where each edge is represented by a pair
+/// of grid coordinates, and converts them into a `MultiLineString`.  Likely there
+/// will only be a single LineString, but if there are self-intersections multiple
+/// LineStrings are needed.  The edges should completely encircle regions.
+///
+/// Where two cells of the region touch only at a corner the boundary crosses itself there,
+/// a figure-eight.  The edges at such a knot are paired up so each ring goes round its own
+/// cell of the region, and a ring that comes back through the knot later is cut there,
+/// so every ring is simple.
+///
+/// # Parameters
+///
+/// - `edges`: A vector of edge segments, where each edge is represented as a pair
+///   of `Coord<usize>` values defining the start and end points.
+///
+/// # Returns
+///
+/// A `MultiLineString<usize>` where input edges have been ordered to make a series
+/// of LineStrings.  Each ring is wound with the region on its left, in the shoelace sense,
+/// so outer rings are counter-clockwise with positive signed area and holes are
+/// clockwise, whatever order the edges came in.
+///
+/// # Panics
+///
+/// If the edges don't make rings, see [`try_edges_to_multilinestring`] to get an error
+/// instead.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, MultiLineString, LineString};
+/// use ndarray::array;
+///
+/// let grid = array![[0]];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(0, &e[&0], &grid);
+/// assert_eq!(mls.0.len(), 1);
+/// assert_eq!(mls.0[0], LineString::from(vec![
+///    Coord { x: 0, y: 0 },
+///    Coord { x: 1, y: 0 },
+///    Coord { x: 1, y: 1 },
+///    Coord { x: 0, y: 1 },
+///    Coord { x: 0, y: 0 },
+/// ]));
+/// let grid = array![
+///     [0, 1],
+///     [1, 1],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(0, &e[&0], &grid);
+/// assert_eq!(mls.0.len(), 1);
+/// assert_eq!(mls.0[0], LineString::from(vec![
+///    Coord { x: 0, y: 0 },
+///    Coord { x: 1, y: 0 },
+///    Coord { x: 1, y: 1 },
+///    Coord { x: 0, y: 1 },
+///    Coord { x: 0, y: 0 },
+/// ]));
+/// let grid = array![
+///     [1, 1],
+///     [1, 1],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(1, &e[&1], &grid);
+/// assert_eq!(mls.0.len(), 1);
+/// assert_eq!(mls.0[0], LineString::from(vec![
+///    Coord { x: 0, y: 0 },
+///    Coord { x: 1, y: 0 },
+///    Coord { x: 2, y: 0 },
+///    Coord { x: 2, y: 1 },
+///    Coord { x: 2, y: 2 },
+///    Coord { x: 1, y: 2 },
+///    Coord { x: 0, y: 2 },
+///    Coord { x: 0, y: 1 },
+///    Coord { x: 0, y: 0 },
+/// ]));
+/// let grid = array![
+///     [0, 1, 0],
+///     [1, 0, 1],
+///     [0, 1, 0],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(1, &e[&1], &grid);
+/// assert_eq!(mls.0.len(), 4);
+/// assert_eq!(mls.0[0], LineString::from(vec![
+///    Coord { x: 1, y: 0 },
+///    Coord { x: 2, y: 0 },
+///    Coord { x: 2, y: 1 },
+///    Coord { x: 1, y: 1 },
+///    Coord { x: 1, y: 0 },
+/// ]));
+/// assert_eq!(mls.0[1], LineString::from(vec![
+///    Coord { x: 1, y: 3 },
+///    Coord { x: 1, y: 2 },
+///    Coord { x: 2, y: 2 },
+///    Coord { x: 2, y: 3 },
+///    Coord { x: 1, y: 3 },
+/// ]));
+/// assert_eq!(mls.0[2], LineString::from(vec![
+///    Coord { x: 0, y: 1 },
+///    Coord { x: 1, y: 1 },
+///    Coord { x: 1, y: 2 },
+///    Coord { x: 0, y: 2 },
+///    Coord { x: 0, y: 1 },
+/// ]));
+/// assert_eq!(mls.0[3], LineString::from(vec![
+///    Coord { x: 3, y: 1 },
+///    Coord { x: 3, y: 2 },
+///    Coord { x: 2, y: 2 },
+///    Coord { x: 2, y: 1 },
+///    Coord { x: 3, y: 1 },
+/// ]));
+///
+/// // the hole in the 1s is a figure-eight, two 0s touching at (2, 2), and comes out as
+/// // two rings meeting there
+/// let grid = array![
+///     [1, 1, 1, 1],
+///     [1, 1, 0, 1],
+///     [1, 0, 1, 1],
+///     [1, 1, 1, 1],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(1, &e[&1], &grid);
+/// assert_eq!(mls.0.len(), 3);
+/// assert!(geospatial::validate_rings(&mls).is_empty());
+/// let knot = Coord { x: 2, y: 2 };
+/// assert_eq!(mls.0.iter().filter(|ls| ls.0.contains(&knot)).count(), 2);
+///
+/// // outer rings are counter-clockwise, holes clockwise
+/// use geo::{Area, Polygon};
+/// let grid = array![
+///     [1, 1, 1],
+///     [1, 0, 1],
+///     [1, 1, 1],
+/// ];
+/// let mut e = geospatial::marching_squares(&grid);
+/// e.get_mut(&1).unwrap().reverse();
+/// let mls = geospatial::edges_to_multilinestring_as::<f64, _>(1, &e[&1], &grid);
+/// let area = |ls: &LineString<f64>| Polygon::new(ls.clone(), vec![]).signed_area();
+/// let mut areas: Vec<f64> = mls.0.iter().map(area).collect();
+/// areas.sort_by(f64::total_cmp);
+/// assert_eq!(areas, vec![-1.0, 9.0]);
+///
+/// // the edges can be any slice, like one region's edges kept in an array
+/// let square = [
+///     (Coord { x: 0, y: 0 }, Coord { x: 1, y: 0 }),
+///     (Coord { x: 1, y: 0 }, Coord { x: 1, y: 1 }),
+///     (Coord { x: 0, y: 1 }, Coord { x: 1, y: 1 }),
+///     (Coord { x: 0, y: 0 }, Coord { x: 0, y: 1 }),
+/// ];
+/// let mls = geospatial::edges_to_multilinestring(1, &square, &array![[1]]);
+/// assert_eq!(geospatial::polygon_area(&mls.0[0]), 1);
+/// ```
+pub fn edges_to_multilinestring<T>(
+    id: T,
+    edges: &[GridEdge],
+    grid: &Array2<T>,
+) -> MultiLineString<usize>
+where
+    T: Eq + Hash + Copy,
+{
+    match try_edges_to_multilinestring(id, edges, grid) {
+        Ok(mls) => mls,
+        Err(e) => panic!("can't assemble edges into rings: {e}"),
+    }
+}
+
+/// How [`edges_to_multilinestring_with`] goes on at a knot, a corner with four edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KnotPolicy {
+    /// Look at the grid and take the sharpest turn towards the region, so the boundary
+    /// hugs it, as [`edges_to_multilinestring`] does.
+    #[default]
+    GridGuided,
+    /// Don't look at the grid: pair off the knot's edges so the two passes through it
+    /// touch without crossing, then cut any ring that comes back through a corner in two
+    /// there.
+    SplitSimple,
+}
+
+/// [`edges_to_multilinestring`], choosing how rings are followed through knots
+///
+/// Where a region touches itself, or a hole touches its outside, at a corner, four edges
+/// meet and the rings can go on along either pairing of them.  With
+/// [`KnotPolicy::GridGuided`] the grid is used to hug the region, exactly as
+/// [`edges_to_multilinestring`] does, so each outer ring is one 4-connected piece.  With
+/// [`KnotPolicy::SplitSimple`] the walk doesn't use the grid at all, and instead cuts
+/// every ring at any corner it passes through twice.  The grid is only used afterwards,
+/// to wind the rings with the region on their left.
+///
+/// # Notes
+///
+/// - Both give simple rings, meeting each other at most at knots.  On a checkerboard,
+///   where every knot is passed twice, they give the same unit squares.
+/// - With `SplitSimple` a knot passed once, by two different rings, is left as the walk
+///   paired it, so pieces that touch at a corner can come out as one ring like with
+///   eight connectivity, and their neighbour on the other diagonal as a hole.
+/// - `SplitSimple` is for edges the grid doesn't describe, like ones that have been
+///   edited or merged from tiles, where there is no region to hug.
+///
+/// # Panics
+///
+/// If the edges don't form closed rings, see [`try_edges_to_multilinestring`].
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use geospatial::KnotPolicy;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 0, 1],
+///     [0, 1, 0],
+///     [1, 0, 1],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// for policy in [KnotPolicy::GridGuided, KnotPolicy::SplitSimple] {
+///     let mls = geospatial::edges_to_multilinestring_with(1, &e[&1], &grid, policy);
+///     // five unit squares, the middle one touching the other four at its corners
+///     assert_eq!(mls.0.len(), 5);
+///     assert!(mls.0.iter().all(|ls| geospatial::polygon_area(ls) == 1));
+///     assert!(geospatial::validate_rings(&mls).is_empty());
+///     let middle = Coord { x: 1, y: 1 };
+///     assert_eq!(mls.0.iter().filter(|ls| ls.0.contains(&middle)).count(), 2);
+/// }
+///
+/// // the bottom corners filled in, so the 0s only pass through two of the knots once
+/// let grid = array![
+///     [1, 0, 1],
+///     [0, 1, 0],
+///     [0, 0, 0],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let areas = |policy| {
+///     let mls = geospatial::edges_to_multilinestring_with(0, &e[&0], &grid, policy);
+///     assert!(geospatial::validate_rings(&mls).is_empty());
+///     let mut a: Vec<i64> = mls.0.iter().map(geospatial::polygon_area).collect();
+///     a.sort();
+///     a
+/// };
+/// // the top 0 apart from the others
+/// assert_eq!(areas(KnotPolicy::GridGuided), vec![1, 5]);
+/// // joined to them through its corners, round the middle 1 as a hole
+/// assert_eq!(areas(KnotPolicy::SplitSimple), vec![-1, 7]);
+/// ```
+pub fn edges_to_multilinestring_with<T>(
+    id: T,
+    edges: &[GridEdge],
+    grid: &Array2<T>,
+    policy: KnotPolicy,
+) -> MultiLineString<usize>
+where
+    T: Eq + Hash + Copy,
+{
+    match try_assemble(id, edges, grid, policy) {
+        Ok(mls) => mls,
+        Err(e) => panic!("can't assemble edges into rings: {e}"),
+    }
+}
+
+/// Why edges could not be assembled into rings, from [`try_edges_to_multilinestring`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeAssemblyError {
+    /// a vertex with other than 2 or 4 edges, so a ring would have to end there
+    DanglingVertex { at: Coord<usize>, degree: usize },
+    /// a ring could not be followed round back to its start, it got stuck at this vertex.
+    /// This happens when the edges don't match the grid, repeated edges for instance
+    UnclosedRing { at: Coord<usize> },
+}
+
+impl std::fmt::Display for EdgeAssemblyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdgeAssemblyError::DanglingVertex { at, degree } => {
+                write!(f, "vertex ({}, {}) has {degree} edges", at.x, at.y)
+            }
+            EdgeAssemblyError::UnclosedRing { at } => {
+                write!(f, "ring does not close, stuck at ({}, {})", at.x, at.y)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EdgeAssemblyError {}
+
+/// [`edges_to_multilinestring`], returning an error rather than panicking on bad edges
+///
+/// Edges straight from [`marching_squares`] always assemble, but ones that have been
+/// filtered or merged may not.  Every vertex is checked first, then each ring as it is
+/// followed.
+///
+/// # Errors
+///
+/// - [`EdgeAssemblyError::DanglingVertex`] if a vertex has other than 2 or 4 edges.  The
+///   first such vertex of `edges` is reported.
+/// - [`EdgeAssemblyError::UnclosedRing`] if a ring can't be followed back to its start.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use geospatial::EdgeAssemblyError;
+/// use ndarray::array;
+///
+/// let grid = array![[1, 0]];
+/// let mut e = geospatial::marching_squares(&grid)[&1].clone();
+/// assert_eq!(geospatial::try_edges_to_multilinestring(1, &e, &grid).unwrap().0.len(), 1);
+///
+/// // take an edge away and the ring has loose ends
+/// e.pop();
+/// let err = geospatial::try_edges_to_multilinestring(1, &e, &grid).unwrap_err();
+/// assert!(matches!(err, EdgeAssemblyError::DanglingVertex { degree: 1, .. }));
+/// ```
+pub fn try_edges_to_multilinestring<T>(
+    id: T,
+    edges: &[GridEdge],
+    grid: &Array2<T>,
+) -> Result<MultiLineString<usize>, EdgeAssemblyError>
+where
+    T: Eq + Hash + Copy,
+{
+    try_assemble(id, edges, grid, KnotPolicy::GridGuided)
+}
+
+// the ring assembly behind edges_to_multilinestring and its variants
+fn try_assemble<T>(
+    id: T,
+    edges: &[GridEdge],
+    grid: &Array2<T>,
+    policy: KnotPolicy,
+) -> Result<MultiLineString<usize>, EdgeAssemblyError>
+where
+    T: Eq + Hash + Copy,
+{
+    // at a vertex with four edges, carry on along the sharpest turn towards the region so
+    // it is hugged, rather than crossing over to another part of the boundary.  the region
+    // side is only known along unit steps, so `left` carries it over diagonal ones
+    fn knot_next(
+        prev: Coord<usize>,
+        cur: Coord<usize>,
+        n: &[Coord<usize>],
+        left: bool,
+    ) -> Option<Coord<usize>> {
+        let (dx, dy) = (cur.x as f64 - prev.x as f64, cur.y as f64 - prev.y as f64);
+        let turn = |p: &Coord<usize>| {
+            let (ex, ey) = (p.x as f64 - cur.x as f64, p.y as f64 - cur.y as f64);
+            let a = (dx * ey - dy * ex).atan2(dx * ex + dy * ey);
+            if left { a } else { -a }
+        };
+        n.iter()
+            .filter(|p| **p != prev)
+            .max_by(|a, b| turn(a).total_cmp(&turn(b)))
+            .copied()
+    }
+
+    // the grid free choice at a knot: its edges in order round it are paired off first
+    // with second and third with fourth, so two passes through it never cross
+    fn knot_pair(
+        prev: Coord<usize>,
+        cur: Coord<usize>,
+        n: &[Coord<usize>],
+    ) -> Option<Coord<usize>> {
+        let angle = |p: &Coord<usize>| (p.y as f64 - cur.y as f64).atan2(p.x as f64 - cur.x as f64);
+        let mut round = n.to_vec();
+        round.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+        let k = round.iter().position(|p| *p == prev)?;
+        Some(round[k ^ 1])
+    }
+
+    // a helper that makes a single ring.  assumes we start at a point with two neighbours
+    // id and grid are used to figure out correct direction at a knot
+    fn aring<T>(
+        adj: &LabelMap<Coord<usize>, Vec<Coord<usize>>>,
+        start: Coord<usize>,
+        id: T,
+        grid: &Array2<T>,
+        policy: KnotPolicy,
+    ) -> Result<Vec<Coord<usize>>, EdgeAssemblyError>
+    where
+        T: Eq + Hash + Copy,
+    {
+        let mut ring: Vec<Coord<usize>> = Vec::new();
+        let mut cur = start;
+        let mut prev: Coord<usize> = adj[&cur][0];
+        // pairing off a knot's edges, coming back to a knot start along the other pair
+        // is only passing through
+        let last = prev;
+        let mut left = true;
+
+        loop {
+            ring.push(cur);
+            let stuck = Err(EdgeAssemblyError::UnclosedRing { at: cur });
+            // a ring visits a vertex at most twice, any more and it's going round in
+            // circles that don't include start
+            if ring.len() > 2 * adj.len() {
+                return stuck;
+            }
+            let n: &[Coord<usize>] = match adj.get(&cur) {
+                Some(n) if n.len() >= 2 => n,
+                _ => return stuck,
+            };
+            if policy == KnotPolicy::GridGuided && (prev.x == cur.x || prev.y == cur.y) {
+                left = cell_left_of(prev, cur, grid).is_some_and(|ix| grid[ix] == id);
+            }
+            let chosen = match policy {
+                _ if n.len() != 4 => None,
+                KnotPolicy::GridGuided => knot_next(prev, cur, n, left),
+                KnotPolicy::SplitSimple => knot_pair(prev, cur, n),
+            };
+            let next = if n.len() == 4 {
+                match chosen {
+                    Some(p) => p,
+                    None => return stuck,
+                }
+            } else if prev == n[0] {
+                n[1]
+            } else if prev == n[1] {
+                n[0]
+            } else {
+                return stuck;
+            };
+            if next == start && (policy == KnotPolicy::GridGuided || cur == last) {
+                break;
+            }
+            prev = cur;
+            cur = next;
+        }
+        ring.push(start);
+
+        Ok(ring)
+    }
+
+    // build the adjacency once, edges are removed from it as they are used up by rings
+    let mut adj = build_adjacency(edges);
+    for (a, b) in edges {
+        for p in [a, b] {
+            let degree = adj[p].len();
+            if degree != 2 && degree != 4 {
+                return Err(EdgeAssemblyError::DanglingVertex { at: *p, degree });
+            }
+        }
+    }
+
+    let mut rings: Vec<LineString<usize>> = Vec::new();
+    for edge in edges {
+        // an edge already walked by a ring is gone from the adjacency
+        if !adj[&edge.0].contains(&edge.1) {
+            continue;
+        }
+
+        // first point of first unused edge will do to make a ring
+        let start = edge.0;
+        let ring = aring::<T>(&adj, start, id, grid, policy)?;
+
+        for w in ring.windows(2) {
+            for (a, b) in [(w[0], w[1]), (w[1], w[0])] {
+                if let Some(n) = adj.get_mut(&a) {
+                    n.retain(|p| *p != b);
+                }
+            }
+        }
+
+        // a ring that comes back through a knot is a figure-eight, cut it there into
+        // simple rings
+        let mut path: Vec<Coord<usize>> = Vec::with_capacity(ring.len());
+        let mut at: LabelMap<Coord<usize>, usize> = LabelMap::default();
+        for p in ring {
+            if let Some(&k) = at.get(&p) {
+                for q in &path[k + 1..] {
+                    at.remove(q);
+                }
+                let mut part = path.split_off(k);
+                part.push(p);
+                rings.push(LineString(part));
+            }
+            at.insert(p, path.len());
+            path.push(p);
+        }
+    }
+
+    // wind every ring with the region on its left, so outer rings are counter-clockwise
+    // and holes clockwise
+    for ring in &mut rings {
+        let (a, b) = first_unit_step(ring);
+        if (a.x == b.x || a.y == b.y) && !cell_left_of(a, b, grid).is_some_and(|ix| grid[ix] == id)
+        {
+            ring.0.reverse();
+        }
+    }
+
+    Ok(MultiLineString::new(rings))
+}
+
+/// [`edges_to_multilinestring`] with the coordinates converted to another numeric type
+///
+/// Saves casting every coordinate by hand when the rings are needed as, for example, `f64`
+/// or `i32`.  Each `usize` is converted with `C::from`.
+///
+/// # Panics
+///
+/// If a coordinate can not be represented in `C`, eg a corner past 127 when `C` is `i8`.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+/// use ndarray::array;
+///
+/// let grid = array![[0]];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring_as::<f64, _>(0, &e[&0], &grid);
+/// assert_eq!(mls.0[0], LineString::from(vec![
+///    Coord { x: 0.0, y: 0.0 },
+///    Coord { x: 1.0, y: 0.0 },
+///    Coord { x: 1.0, y: 1.0 },
+///    Coord { x: 0.0, y: 1.0 },
+///    Coord { x: 0.0, y: 0.0 },
+/// ]));
+/// ```
+pub fn edges_to_multilinestring_as<C, T>(
+    id: T,
+    edges: &[GridEdge],
+    grid: &Array2<T>,
+) -> MultiLineString<C>
+where
+    C: CoordNum,
+    T: Eq + Hash + Copy,
+{
+    let conv = |v: usize| C::from(v).expect("grid coordinate does not fit in the output type");
+    MultiLineString::new(
+        edges_to_multilinestring(id, edges, grid)
+            .0
+            .into_iter()
+            .map(|ls| {
+                ls.0.into_iter()
+                    .map(|c| Coord {
+                        x: conv(c.x),
+                        y: conv(c.y),
+                    })
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+/// Sorts the rings of a `MultiLineString` biggest first, for output that doesn't depend on
+/// `HashMap` order
+///
+/// Rings are ordered by descending magnitude of their signed area, so an exterior comes
+/// before the holes inside it.  Ties go to the ring with the smallest vertex, by `(y, x)`,
+/// and then to the vertices in order, so the result only depends on the set of rings.  The
+/// vertices within each ring are left alone.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{LineString, MultiLineString};
+///
+/// let small = LineString::from(vec![(5, 5), (6, 5), (6, 6), (5, 6), (5, 5)]);
+/// let other = LineString::from(vec![(0, 0), (1, 0), (1, 1), (0, 1), (0, 0)]);
+/// let big = LineString::from(vec![(0, 2), (3, 2), (3, 4), (0, 4), (0, 2)]);
+/// let mut mls = MultiLineString::new(vec![small.clone(), big.clone(), other.clone()]);
+/// geospatial::sort_rings_by_area(&mut mls);
+/// assert_eq!(mls.0, vec![big, other, small]);
+/// ```
+pub fn sort_rings_by_area(mls: &mut MultiLineString<usize>) {
+    mls.0.sort_by_cached_key(|ls| {
+        let area = twice_signed_area(&ls.0).unsigned_abs();
+        let min = ls.0.iter().map(|c| (c.y, c.x)).min();
+        let coords: Vec<(usize, usize)> = ls.0.iter().map(|c| (c.y, c.x)).collect();
+        (std::cmp::Reverse(area), min, coords)
+    });
+}
+
+/// Removes the vertices in the middle of straight runs, without moving any ring
+///
+/// Rings from [`edges_to_multilinestring`] have a vertex at every cell corner, so a long
+/// straight side has one per cell.  A vertex exactly on the segment between its neighbours
+/// adds nothing, and with integer vertices that is an exact test, so this is a lossless
+/// step to run before [`simplify_dp_ring`] or writing the rings out.
+///
+/// # Returns
+///
+/// The same rings with the same orientation.  A closed ring stays closed, and if its first
+/// vertex was in the middle of a run it starts at the next corner instead.  An open line
+/// keeps both its ends.
+///
+/// # Notes
+///
+/// - Repeated vertices go too, but a spike that doubles back on itself is kept, its tip
+///   isn't between its neighbours.
+/// - Diagonal runs, as from [`Connectivity::Eight`], collapse the same way.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{LineString, MultiLineString};
+/// use ndarray::Array2;
+///
+/// let grid = Array2::from_elem((3, 4), 1);
+/// let edges = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(1, &edges[&1], &grid);
+/// assert_eq!(mls.0[0].0.len(), 15);
+/// let tidy = geospatial::collapse_colinear(&mls);
+/// assert_eq!(tidy.0[0].0.len(), 5);
+/// assert!(tidy.0[0].is_closed());
+/// assert_eq!(geospatial::polygon_area(&tidy.0[0]), geospatial::polygon_area(&mls.0[0]));
+/// ```
+pub fn collapse_colinear(mls: &MultiLineString<usize>) -> MultiLineString<usize> {
+    let between = |a: Coord<usize>, b: Coord<usize>, p: Coord<usize>| {
+        orient(a, b, p) == 0 && on_segment(a, b, p)
+    };
+    let collapse = |ls: &LineString<usize>| -> LineString<usize> {
+        let closed = ls.0.len() > 3 && ls.is_closed();
+        let pts = if closed {
+            &ls.0[..ls.0.len() - 1]
+        } else {
+            &ls.0[..]
+        };
+        let mut out: Vec<Coord<usize>> = Vec::with_capacity(pts.len() + 1);
+        for &p in pts {
+            while let [.., a, b] = out[..]
+                && between(a, p, b)
+            {
+                out.pop();
+            }
+            out.push(p);
+        }
+        if closed {
+            // the run through the closing point
+            while out.len() > 3 && between(out[out.len() - 2], out[0], out[out.len() - 1]) {
+                out.pop();
+            }
+            while out.len() > 3 && between(out[out.len() - 1], out[1], out[0]) {
+                out.remove(0);
+            }
+            if out.len() < 3 {
+                return ls.clone();
+            }
+            out.push(out[0]);
+        }
+        LineString::new(out)
+    };
+    MultiLineString::new(mls.0.iter().map(collapse).collect())
+}
+
+/// Chains edges into polylines without assuming they close up
+///
+/// [`edges_to_multilinestring`] expects edges that form rings around regions.  This takes
+/// any set of edges, eg from a contour or a single shared boundary, and chains them into as
+/// few polylines as it can.  A chain only starts and ends at a vertex with an odd number
+/// of edges, such as a dead end with just one, and carries on through every other
+/// vertex.  Once no such vertices are left, the remaining edges form loops and come out as
+/// closed `LineString`s.
+///
+/// # Returns
+///
+/// The open polylines, in the order their first edges appear in `edges`, then the closed
+/// ones.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let c = |x, y| Coord { x, y };
+/// let edges = vec![
+///     (c(1, 0), c(2, 0)),
+///     (c(0, 0), c(1, 0)),
+///     (c(2, 0), c(2, 1)),
+///     // a separate square
+///     (c(5, 5), c(6, 5)),
+///     (c(6, 5), c(6, 6)),
+///     (c(5, 6), c(6, 6)),
+///     (c(5, 5), c(5, 6)),
+/// ];
+/// let mls = geospatial::assemble_open(&edges);
+/// assert_eq!(mls.0, vec![
+///     LineString::from(vec![(0, 0), (1, 0), (2, 0), (2, 1)]),
+///     LineString::from(vec![(5, 5), (6, 5), (6, 6), (5, 6), (5, 5)]),
+/// ]);
+/// ```
+pub fn assemble_open(edges: &[GridEdge]) -> MultiLineString<usize> {
+    let mut adj: LabelMap<Coord<usize>, Vec<(Coord<usize>, usize)>> = LabelMap::default();
+    for (i, e) in edges.iter().enumerate() {
+        adj.entry(e.0).or_default().push((e.1, i));
+        adj.entry(e.1).or_default().push((e.0, i));
+    }
+    let mut used = vec![false; edges.len()];
+    let walk = |start: Coord<usize>, used: &mut [bool]| {
+        let mut line = vec![start];
+        let mut cur = start;
+        while let Some(&(next, i)) = adj[&cur].iter().find(|(_, i)| !used[*i]) {
+            used[i] = true;
+            line.push(next);
+            cur = next;
+        }
+        LineString::new(line)
+    };
+
+    let mut out = Vec::new();
+    for e in edges {
+        for v in [e.0, e.1] {
+            // odd among the edges still left, a trail has ended here
+            let unused = adj[&v].iter().filter(|(_, i)| !used[*i]).count();
+            if unused % 2 == 1 {
+                out.push(walk(v, &mut used));
+            }
+        }
+    }
+    for (i, e) in edges.iter().enumerate() {
+        if !used[i] {
+            out.push(walk(e.0, &mut used));
+        }
+    }
+    MultiLineString::new(out)
+}
+
+/// A problem with one ring, found by [`validate_rings`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingError {
+    /// the first and last points differ
+    NotClosed { ring: usize },
+    /// fewer than the 4 points a closed ring needs
+    TooShort { ring: usize, points: usize },
+    /// the segments starting at these points cross or touch, other than where neighbouring
+    /// segments join
+    SelfIntersection {
+        ring: usize,
+        segments: (usize, usize),
+    },
+    /// closed, but enclosing no area
+    ZeroArea { ring: usize },
+    /// wound the same way as the ring it is nested directly inside, so a hole that looks
+    /// like another exterior or the other way round
+    WrongWinding { ring: usize, parent: usize },
+}
+
+impl std::fmt::Display for RingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RingError::NotClosed { ring } => write!(f, "ring {ring} is not closed"),
+            RingError::TooShort { ring, points } => {
+                write!(f, "ring {ring} has only {points} points")
+            }
+            RingError::SelfIntersection { ring, segments } => write!(
+                f,
+                "ring {ring} intersects itself between segments {} and {}",
+                segments.0, segments.1
+            ),
+            RingError::ZeroArea { ring } => write!(f, "ring {ring} has no area"),
+            RingError::WrongWinding { ring, parent } => {
+                write!(
+                    f,
+                    "ring {ring} is wound the same way as ring {parent} around it"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RingError {}
+
+// twice the signed area of the triangle abc, the sign says which side of ab c is on
+fn orient(a: Coord<usize>, b: Coord<usize>, c: Coord<usize>) -> i128 {
+    let (ax, ay) = (a.x as i128, a.y as i128);
+    (b.x as i128 - ax) * (c.y as i128 - ay) - (b.y as i128 - ay) * (c.x as i128 - ax)
+}
+
+// whether p, known to be colinear with ab, lies on the closed segment ab
+fn on_segment(a: Coord<usize>, b: Coord<usize>, p: Coord<usize>) -> bool {
+    a.x.min(b.x) <= p.x && p.x <= a.x.max(b.x) && a.y.min(b.y) <= p.y && p.y <= a.y.max(b.y)
+}
+
+// whether the closed segments ab and cd have any point in common
+fn segments_meet(a: Coord<usize>, b: Coord<usize>, c: Coord<usize>, d: Coord<usize>) -> bool {
+    let (o1, o2) = (orient(a, b, c).signum(), orient(a, b, d).signum());
+    let (o3, o4) = (orient(c, d, a).signum(), orient(c, d, b).signum());
+    (o1 * o2 < 0 && o3 * o4 < 0)
+        || (o1 == 0 && on_segment(a, b, c))
+        || (o2 == 0 && on_segment(a, b, d))
+        || (o3 == 0 && on_segment(c, d, a))
+        || (o4 == 0 && on_segment(c, d, b))
+}
+
+// pairs of segments of a ring that meet somewhere they shouldn't, by the index of their
+// first points.  segments are bucketed by the unit cells their bounding boxes cover so
+// only nearby ones are compared
+fn ring_self_intersections(ring: &[Coord<usize>]) -> Vec<(usize, usize)> {
+    // drop repeated points, remembering where each segment started in the original
+    let mut pts: Vec<(Coord<usize>, usize)> = Vec::with_capacity(ring.len());
+    for (i, p) in ring.iter().enumerate() {
+        if pts.last().is_none_or(|l| l.0 != *p) {
+            pts.push((*p, i));
+        }
+    }
+    let nseg = pts.len().saturating_sub(1);
+    let closed = nseg > 1 && pts[0].0 == pts[nseg].0;
+    let seg = |k: usize| (pts[k].0, pts[k + 1].0);
+
+    let mut buckets: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for k in 0..nseg {
+        let (a, b) = seg(k);
+        for x in a.x.min(b.x)..=a.x.max(b.x) {
+            for y in a.y.min(b.y)..=a.y.max(b.y) {
+                buckets.entry((x, y)).or_default().push(k);
+            }
+        }
+    }
+
+    let mut found: HashSet<(usize, usize)> = HashSet::new();
+    for ks in buckets.values() {
+        for (n, &i) in ks.iter().enumerate() {
+            for &j in &ks[n + 1..] {
+                let (i, j) = (i.min(j), i.max(j));
+                let ((a, b), (c, d)) = (seg(i), seg(j));
+                let bad = if j == i + 1 || (closed && i == 0 && j == nseg - 1) {
+                    // neighbours always share a point, they only go wrong by doubling back
+                    let (p, q, r) = if j == i + 1 { (a, b, d) } else { (c, d, b) };
+                    orient(p, q, r) == 0
+                        && (q.x as i128 - p.x as i128) * (r.x as i128 - q.x as i128)
+                            + (q.y as i128 - p.y as i128) * (r.y as i128 - q.y as i128)
+                            < 0
+                } else {
+                    segments_meet(a, b, c, d)
+                };
+                if bad {
+                    found.insert((pts[i].1, pts[j].1));
+                }
+            }
+        }
+    }
+    let mut found: Vec<(usize, usize)> = found.into_iter().collect();
+    found.sort();
+    found
+}
+
+/// Checks the rings of a `MultiLineString` are valid for `geo`
+///
+/// Each ring should be closed (its last point the same as its first), have at least 4
+/// points, enclose some area, and be simple, never crossing or touching itself.  A ring
+/// nested inside another should also be wound the other way, as holes are.  Run this over
+/// the output of [`edges_to_multilinestring`] to catch bad rings in a test rather than
+/// getting silently wrong polygons later.
+///
+/// # Returns
+///
+/// Every problem found, in ring order.  A ring can have more than one problem, and each
+/// pair of segments that meet is reported once.  An empty `Vec` means every ring is fine.
+///
+/// # Notes
+///
+/// - A ring through a pinch point, where a region touches itself diagonally, visits that
+///   corner twice, and is reported as a self intersection.
+/// - Repeated consecutive points aren't an error, they are skipped over.
+/// - A ring's parent is the smallest other ring around the midpoint of its first segment.
+///   Only closed rings with some area take part, and each ring is tested against all the
+///   others, so this is quadratic in the number of rings.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString, MultiLineString};
+/// use geospatial::RingError;
+///
+/// let square = LineString::from(vec![(0, 0), (1, 0), (1, 1), (0, 1), (0, 0)]);
+/// let open = LineString::from(vec![(0, 0), (1, 0), (1, 1), (0, 1)]);
+/// let bowtie = LineString::from(vec![(0, 0), (2, 2), (2, 0), (0, 2), (0, 0)]);
+/// let short = LineString::from(vec![(0, 0), (1, 0), (0, 0)]);
+/// let mls = MultiLineString::new(vec![square, open, bowtie, short]);
+/// assert_eq!(
+///     geospatial::validate_rings(&mls),
+///     vec![
+///         RingError::NotClosed { ring: 1 },
+///         RingError::ZeroArea { ring: 2 },
+///         RingError::SelfIntersection { ring: 2, segments: (0, 2) },
+///         RingError::TooShort { ring: 3, points: 3 },
+///         RingError::SelfIntersection { ring: 3, segments: (0, 1) },
+///     ]
+/// );
+///
+/// // a hole wound the same way as its exterior
+/// let outer = LineString::from(vec![(0, 0), (3, 0), (3, 3), (0, 3), (0, 0)]);
+/// let hole = LineString::from(vec![(1, 1), (2, 1), (2, 2), (1, 2), (1, 1)]);
+/// let mls = MultiLineString::new(vec![outer.clone(), hole.clone()]);
+/// assert_eq!(
+///     geospatial::validate_rings(&mls),
+///     vec![RingError::WrongWinding { ring: 1, parent: 0 }]
+/// );
+/// let hole = LineString::new(hole.0.into_iter().rev().collect());
+/// assert!(geospatial::validate_rings(&MultiLineString::new(vec![outer, hole])).is_empty());
+/// ```
+pub fn validate_rings(mls: &MultiLineString<usize>) -> Vec<RingError> {
+    // the signed areas of the closed rings, the only ones that can nest
+    let areas: Vec<Option<i128>> = mls
+        .0
+        .iter()
+        .map(|ls| (ls.0.len() >= 4 && ls.is_closed()).then(|| twice_signed_area(&ls.0)))
+        .collect();
+    let parent = |ring: usize, area: i128| -> Option<usize> {
+        let ls = &mls.0[ring];
+        let (a, b) =
+            ls.0.windows(2)
+                .map(|w| (w[0], w[1]))
+                .find(|(a, b)| a != b)?;
+        let probe = Coord {
+            x: (a.x + b.x) as f64 / 2.0,
+            y: (a.y + b.y) as f64 / 2.0,
+        };
+        areas
+            .iter()
+            .enumerate()
+            .filter_map(|(j, other)| Some((j, other.filter(|&o| o != 0)?.abs())))
+            .filter(|&(j, other)| {
+                j != ring && other > area.abs() && ring_contains(&mls.0[j].0, probe)
+            })
+            .min_by_key(|&(_, other)| other)
+            .map(|(j, _)| j)
+    };
+
+    let mut errors = Vec::new();
+    for (ring, ls) in mls.0.iter().enumerate() {
+        if ls.0.first() != ls.0.last() {
+            errors.push(RingError::NotClosed { ring });
+        }
+        if ls.0.len() < 4 {
+            errors.push(RingError::TooShort {
+                ring,
+                points: ls.0.len(),
+            });
+        }
+        if areas[ring] == Some(0) {
+            errors.push(RingError::ZeroArea { ring });
+        }
+        for segments in ring_self_intersections(&ls.0) {
+            errors.push(RingError::SelfIntersection { ring, segments });
+        }
+        if let Some(area) = areas[ring].filter(|&a| a != 0)
+            && let Some(p) = parent(ring, area)
+            && areas[p].is_some_and(|pa| (pa > 0) == (area > 0))
+        {
+            errors.push(RingError::WrongWinding { ring, parent: p });
+        }
+    }
+    errors
+}
+
+/// Which neighbouring cells are considered connected to a cell.
+///
+/// `Four` only connects cells that share an edge (up, down, left and right), `Eight` also
+/// connects cells that only touch at a corner.  The same type is used by everything that
+/// needs a choice, labelling, flood fill and morphology, so one value can be passed
+/// through a whole pipeline.  The default is `Four`, which is what [`marching_squares`]
+/// and [`trace_regions`] always use, [`marching_squares_connectivity`] takes either.
+///
+/// # Examples
+///
+/// ```
+/// use geospatial::Connectivity;
+///
+/// assert_eq!(Connectivity::default(), Connectivity::Four);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Connectivity {
+    #[default]
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    // (row, col) offsets of every neighbour
+    fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Connectivity::Four => &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Connectivity::Eight => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+        }
+    }
+
+    // (row, col) offsets of the neighbours already visited in a row-major scan
+    fn prior_offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Connectivity::Four => &[(-1, 0), (0, -1)],
+            Connectivity::Eight => &[(-1, -1), (-1, 0), (-1, 1), (0, -1)],
+        }
+    }
+}
+
+/// Connected components labeling of a boolean mask
+///
+/// Gives every connected blob of `true` cells its own label, so the result can be fed
+/// straight into [`marching_squares`].  A two pass union-find is used.
+///
+/// # Parameters
+///
+/// - `mask`: cells that are `true` are foreground, `false` are background
+/// - `connectivity`: whether diagonal neighbours join blobs together
+///
+/// # Returns
+///
+/// An `Array2<u32>` the same shape as `mask`.  Background cells are `0` and the blobs are
+/// labelled `1..=n` in the order their first cell is met scanning row by row.  `u32` keeps
+/// big label grids at half the size of `usize` ones, and is still room for billions of
+/// blobs.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+/// use geospatial::{label_components, Connectivity};
+///
+/// let mask = array![
+///     [true, false, true],
+///     [false, true, false],
+///     [true, true, false],
+/// ];
+/// assert_eq!(
+///     label_components(&mask, Connectivity::Four),
+///     array![
+///         [1, 0, 2],
+///         [0, 3, 0],
+///         [3, 3, 0],
+///     ]
+/// );
+/// assert_eq!(
+///     label_components(&mask, Connectivity::Eight),
+///     array![
+///         [1, 0, 1],
+///         [0, 1, 0],
+///         [1, 1, 0],
+///     ]
+/// );
+///
+/// // a u shape only joins up on the last row
+/// let mask = array![
+///     [true, false, true],
+///     [true, true, true],
+/// ];
+/// assert_eq!(
+///     label_components(&mask, Connectivity::Four),
+///     array![
+///         [1, 0, 1],
+///         [1, 1, 1],
+///     ]
+/// );
+///
+/// // mask to labels to edges to polygons
+/// let mask = array![
+///     [true, true, false],
+///     [false, false, false],
+///     [false, true, true],
+/// ];
+/// let labels = label_components(&mask, Connectivity::Four);
+/// let edges = geospatial::marching_squares(&labels);
+/// let blob = geospatial::region_to_multipolygon(2, &edges[&2], &labels);
+/// assert_eq!(blob.0.len(), 1);
+/// assert_eq!(blob.0[0].exterior().0.len(), 7);
+/// ```
+pub fn label_components(mask: &Array2<bool>, connectivity: Connectivity) -> Array2<u32> {
+    fn find(parent: &mut [u32], mut x: u32) -> u32 {
+        while parent[x as usize] != x {
+            parent[x as usize] = parent[parent[x as usize] as usize];
+            x = parent[x as usize];
+        }
+        x
+    }
+
+    let (nrows, ncols) = mask.dim();
+    let mut labels: Array2<u32> = Array2::zeros((nrows, ncols));
+
+    // parent[0] is the background, and is never joined to anything
+    let mut parent: Vec<u32> = vec![0];
+
+    // first pass gives provisional labels, joining any that meet
+    for r in 0..nrows {
+        for c in 0..ncols {
+            if !mask[[r, c]] {
+                continue;
+            }
+            let mut mine = 0;
+            for (dr, dc) in connectivity.prior_offsets() {
+                let (nr, nc) = (r as isize + dr, c as isize + dc);
+                if nr < 0 || nc < 0 || nc >= ncols as isize {
+                    continue;
+                }
+                let theirs = labels[[nr as usize, nc as usize]];
+                if theirs == 0 {
+                    continue;
+                }
+                if mine == 0 {
+                    mine = theirs;
+                } else {
+                    // keep the smaller root so roots stay in scan order
+                    let (a, b) = (find(&mut parent, mine), find(&mut parent, theirs));
+                    parent[a.max(b) as usize] = a.min(b);
+                }
+            }
+            if mine == 0 {
+                mine = parent.len() as u32;
+                parent.push(mine);
+            }
+            labels[[r, c]] = mine;
+        }
+    }
+
+    // second pass resolves every label to its root, numbering the roots consecutively
+    let mut renumber: Vec<u32> = vec![0; parent.len()];
+    let mut next = 0;
+    for l in labels.iter_mut() {
+        if *l == 0 {
+            continue;
+        }
+        let root = find(&mut parent, *l) as usize;
+        if renumber[root] == 0 {
+            next += 1;
+            renumber[root] = next;
+        }
+        *l = renumber[root];
+    }
+
+    labels
+}
+
+/// Flood fill from a seed cell
+///
+/// Finds every cell that can be reached from `seed` by stepping between neighbours that have
+/// the same value as the seed.
+///
+/// # Parameters
+///
+/// - `grid`: A 2D array of values representing labeled regions.
+/// - `seed`: The starting cell, `x` is the column and `y` the row.
+/// - `connectivity`: whether diagonal steps are allowed
+///
+/// # Returns
+///
+/// A `Vec<Coord<usize>>` of the cells in the region, in the order a breadth first search
+/// reaches them, starting with `seed`.  If `seed` is outside the grid the `Vec` is empty.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+/// use geospatial::{flood_fill, Connectivity};
+///
+/// let grid = array![
+///     [1, 2, 2],
+///     [2, 1, 2],
+///     [1, 1, 2],
+/// ];
+/// assert_eq!(
+///     flood_fill(&grid, Coord { x: 1, y: 1 }, Connectivity::Four),
+///     vec![Coord { x: 1, y: 1 }, Coord { x: 1, y: 2 }, Coord { x: 0, y: 2 }]
+/// );
+/// assert_eq!(
+///     flood_fill(&grid, Coord { x: 1, y: 1 }, Connectivity::Eight),
+///     vec![
+///         Coord { x: 1, y: 1 },
+///         Coord { x: 0, y: 0 },
+///         Coord { x: 0, y: 2 },
+///         Coord { x: 1, y: 2 },
+///     ]
+/// );
+/// assert_eq!(flood_fill(&grid, Coord { x: 3, y: 0 }, Connectivity::Four), vec![]);
+/// ```
+pub fn flood_fill<T>(
+    grid: &Array2<T>,
+    seed: Coord<usize>,
+    connectivity: Connectivity,
+) -> Vec<Coord<usize>>
+where
+    T: Eq + Copy,
+{
+    let (nrows, ncols) = grid.dim();
+    if seed.x >= ncols || seed.y >= nrows {
+        return Vec::new();
+    }
+    let id = grid[[seed.y, seed.x]];
+
+    let mut seen: Array2<bool> = Array2::from_elem((nrows, ncols), false);
+    let mut out = Vec::new();
+    let mut todo = VecDeque::from([seed]);
+    seen[[seed.y, seed.x]] = true;
+
+    while let Some(c) = todo.pop_front() {
+        out.push(c);
+        for (dr, dc) in connectivity.offsets() {
+            let (nr, nc) = (c.y as isize + dr, c.x as isize + dc);
+            if nr < 0 || nc < 0 || nr >= nrows as isize || nc >= ncols as isize {
+                continue;
+            }
+            let (nr, nc) = (nr as usize, nc as usize);
+            if !seen[[nr, nc]] && grid[[nr, nc]] == id {
+                seen[[nr, nc]] = true;
+                todo.push_back(Coord { x: nc, y: nr });
+            }
+        }
+    }
+
+    out
+}
+
+/// An affine transform from grid (pixel) space to world space
+///
+/// Uses the same six coefficients as a GDAL geotransform, mapping a grid coordinate
+/// `(col, row)` to
+///
+/// ```text
+/// x = a * col + b * row + c
+/// y = d * col + e * row + f
+/// ```
+///
+/// so a GDAL geotransform `gt` is `Affine { a: gt[1], b: gt[2], c: gt[0], d: gt[4], e: gt[5], f: gt[3] }`,
+/// see [`Affine::from_gdal`].
+/// Grid coordinates are cell corners, so `(0, 0)` is the outside corner of the first cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    /// width of a cell
+    pub a: f64,
+    /// row rotation
+    pub b: f64,
+    /// x of the grid origin
+    pub c: f64,
+    /// column rotation
+    pub d: f64,
+    /// height of a cell, negative for north up rasters
+    pub e: f64,
+    /// y of the grid origin
+    pub f: f64,
+}
+
+impl Affine {
+    /// A north up transform with square cells
+    ///
+    /// `origin` is the world position of the top left corner of the grid and `cell` is the
+    /// width of a cell.  Rows increase southwards so `e` is `-cell`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use geo::Coord;
+    /// use geospatial::Affine;
+    ///
+    /// let t = Affine::from_origin_cell(Coord { x: 100.0, y: 50.0 }, 10.0);
+    /// assert_eq!(t, Affine { a: 10.0, b: 0.0, c: 100.0, d: 0.0, e: -10.0, f: 50.0 });
+    /// ```
+    pub fn from_origin_cell(origin: Coord<f64>, cell: f64) -> Self {
+        Affine {
+            a: cell,
+            b: 0.0,
+            c: origin.x,
+            d: 0.0,
+            e: -cell,
+            f: origin.y,
+        }
+    }
+
+    /// From a GDAL geotransform, `[c, a, b, f, d, e]` in the names here
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use geospatial::Affine;
+    ///
+    /// let t = Affine::from_gdal([100.0, 10.0, 0.0, 50.0, 0.0, -10.0]);
+    /// assert_eq!(t, Affine { a: 10.0, b: 0.0, c: 100.0, d: 0.0, e: -10.0, f: 50.0 });
+    /// ```
+    pub fn from_gdal(gt: [f64; 6]) -> Self {
+        Affine {
+            a: gt[1],
+            b: gt[2],
+            c: gt[0],
+            d: gt[4],
+            e: gt[5],
+            f: gt[3],
+        }
+    }
+
+    /// Maps a single grid coordinate to world space.
+    pub fn apply(&self, p: Coord<f64>) -> Coord<f64> {
+        Coord {
+            x: self.a * p.x + self.b * p.y + self.c,
+            y: self.d * p.x + self.e * p.y + self.f,
+        }
+    }
+
+    /// The transform back from world space to grid space
+    ///
+    /// `None` if the transform is singular, squashing the grid onto a line or a point, or
+    /// isn't finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use geo::Coord;
+    /// use geospatial::Affine;
+    ///
+    /// let t = Affine::from_origin_cell(Coord { x: 100.0, y: 50.0 }, 10.0);
+    /// let p = t.inverse().unwrap().apply(Coord { x: 125.0, y: 35.0 });
+    /// assert_eq!(p, Coord { x: 2.5, y: 1.5 });
+    /// assert_eq!(Affine { a: 0.0, ..t }.inverse(), None);
+    /// ```
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.a * self.e - self.b * self.d;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let (a, b) = (self.e / det, -self.b / det);
+        let (d, e) = (-self.d / det, self.a / det);
+        let inv = Affine {
+            a,
+            b,
+            c: -(a * self.c + b * self.f),
+            d,
+            e,
+            f: -(d * self.c + e * self.f),
+        };
+        [inv.a, inv.b, inv.c, inv.d, inv.e, inv.f]
+            .iter()
+            .all(|v| v.is_finite())
+            .then_some(inv)
+    }
+
+    /// Maps every coordinate of a `MultiLineString` from grid to world space
+    ///
+    /// Intended for the output of [`edges_to_multilinestring`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use geo::{Coord, LineString, MultiLineString};
+    /// use ndarray::array;
+    /// use geospatial::Affine;
+    ///
+    /// let grid = array![[0]];
+    /// let e = geospatial::marching_squares(&grid);
+    /// let mls = geospatial::edges_to_multilinestring(0, &e[&0], &grid);
+    /// let t = Affine::from_origin_cell(Coord { x: 100.0, y: 50.0 }, 10.0);
+    /// assert_eq!(
+    ///     t.apply_mls(&mls),
+    ///     MultiLineString::new(vec![LineString::from(vec![
+    ///         Coord { x: 100.0, y: 50.0 },
+    ///         Coord { x: 110.0, y: 50.0 },
+    ///         Coord { x: 110.0, y: 40.0 },
+    ///         Coord { x: 100.0, y: 40.0 },
+    ///         Coord { x: 100.0, y: 50.0 },
+    ///     ])])
+    /// );
+    /// ```
+    pub fn apply_mls(&self, mls: &MultiLineString<usize>) -> MultiLineString<f64> {
+        MultiLineString::new(
+            mls.0
+                .iter()
+                .map(|ls| {
+                    ls.0.iter()
+                        .map(|p| {
+                            self.apply(Coord {
+                                x: p.x as f64,
+                                y: p.y as f64,
+                            })
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Maps rings from [`edges_to_multilinestring`] to world space with a GDAL geotransform
+///
+/// The same as [`Affine::from_gdal`] then [`Affine::apply_mls`], for when the transform
+/// comes straight from GDAL as `[c, a, b, f, d, e]`, so `x = a * col + b * row + c` and
+/// `y = d * col + e * row + f`.
+///
+/// # Notes
+///
+/// - Ring coordinates are cell corners, coordinate `n` being the line between cells
+///   `n - 1` and `n`.  So corner `(0, 0)` goes to the geotransform's origin, the outside
+///   corner of the first cell, and no half cell shift is needed.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+/// use ndarray::array;
+///
+/// let grid = array![[1, 0]];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(1, &e[&1], &grid);
+///
+/// // 10 unit cells, north up, top left corner at (100, 50)
+/// let world = geospatial::apply_affine(&mls, &[100.0, 10.0, 0.0, 50.0, 0.0, -10.0]);
+/// assert_eq!(world.0[0], LineString::from(vec![
+///     Coord { x: 100.0, y: 50.0 },
+///     Coord { x: 110.0, y: 50.0 },
+///     Coord { x: 110.0, y: 40.0 },
+///     Coord { x: 100.0, y: 40.0 },
+///     Coord { x: 100.0, y: 50.0 },
+/// ]));
+/// ```
+pub fn apply_affine(mls: &MultiLineString<usize>, transform: &[f64; 6]) -> MultiLineString<f64> {
+    Affine::from_gdal(*transform).apply_mls(mls)
+}
+
+/// The label of the cell a world space point falls in
+///
+/// For looking up which region a point is in straight from the labelled grid, without
+/// tracing any polygons.  The point is taken back to grid space through the inverse of
+/// the GDAL geotransform `transform`, `[c, a, b, f, d, e]` as for [`apply_affine`], and
+/// floored to a cell.
+///
+/// # Returns
+///
+/// The label of that cell, or `None` if the point is outside the grid, or the transform is
+/// singular and there is no way back to the grid.
+///
+/// # Notes
+///
+/// - A point exactly on the line between two cells is in the one to its right or below,
+///   in grid space, so points on the grid's right or bottom edge are outside it.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 2],
+///     [3, 4],
+/// ];
+/// // 10 unit cells, north up, top left corner at (100, 50)
+/// let gt = [100.0, 10.0, 0.0, 50.0, 0.0, -10.0];
+/// assert_eq!(geospatial::sample_region(&grid, &gt, Coord { x: 115.0, y: 35.0 }), Some(4));
+/// assert_eq!(geospatial::sample_region(&grid, &gt, Coord { x: 100.0, y: 50.0 }), Some(1));
+/// assert_eq!(geospatial::sample_region(&grid, &gt, Coord { x: 95.0, y: 45.0 }), None);
+/// assert_eq!(geospatial::sample_region(&grid, &[0.0; 6], Coord { x: 0.0, y: 0.0 }), None);
+/// ```
+pub fn sample_region<T: Copy>(grid: &Array2<T>, transform: &[f64; 6], pt: Coord<f64>) -> Option<T> {
+    let p = Affine::from_gdal(*transform).inverse()?.apply(pt);
+    let (col, row) = (p.x.floor(), p.y.floor());
+    if col < 0.0 || row < 0.0 || !col.is_finite() || !row.is_finite() {
+        return None;
+    }
+    grid.get([row as usize, col as usize]).copied()
+}
+
+/// Moves rings from grid corner coordinates to cell center coordinates
+///
+/// [`marching_squares`] puts edges on cell corners, corner `n` being the line between
+/// cells `n - 1` and `n`, so the center of cell `(x, y)` is at `(x + 0.5, y + 0.5)`.
+/// Viewers and functions like [`rasterize_linestring`] put cell `(x, y)` at `(x, y)`
+/// instead, and rings drawn there look half a cell too far right and down.  This takes
+/// half a cell off both coordinates, so the ring round cell `(0, 0)` goes from `-0.5` to
+/// `0.5`.
+///
+/// # Parameters
+///
+/// - `mls`: Rings in corner coordinates, scaled so a cell is `cell` wide, eg from
+///   [`edges_to_multilinestring_as`] with `cell` 1.
+/// - `cell`: The width of a cell.
+///
+/// # Notes
+///
+/// - Don't shift rings that are going through an [`Affine`] or [`apply_affine`], a GDAL
+///   geotransform already expects corner coordinates.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+/// use ndarray::array;
+///
+/// let grid = array![[0]];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring_as::<f64, _>(0, &e[&0], &grid);
+/// assert_eq!(geospatial::shift_to_centers(&mls, 1.0).0[0], LineString::from(vec![
+///     Coord { x: -0.5, y: -0.5 },
+///     Coord { x: 0.5, y: -0.5 },
+///     Coord { x: 0.5, y: 0.5 },
+///     Coord { x: -0.5, y: 0.5 },
+///     Coord { x: -0.5, y: -0.5 },
+/// ]));
+/// ```
+pub fn shift_to_centers(mls: &MultiLineString<f64>, cell: f64) -> MultiLineString<f64> {
+    let half = cell / 2.0;
+    mls.map_coords(|c| Coord {
+        x: c.x - half,
+        y: c.y - half,
+    })
+}
+
+// twice the signed area of a ring by the shoelace formula, positive when counter-clockwise
+fn twice_signed_area(ring: &[Coord<usize>]) -> i128 {
+    ring.windows(2)
+        .map(|w| w[0].x as i128 * w[1].y as i128 - w[1].x as i128 * w[0].y as i128)
+        .sum()
+}
+
+// copy of a ring that is closed and wound counter-clockwise (or clockwise if !ccw)
+fn wound_ring(ring: &LineString<usize>, ccw: bool) -> Vec<Coord<usize>> {
+    let mut coords = ring.0.clone();
+    if coords.first() != coords.last() {
+        coords.push(coords[0]);
+    }
+    if (twice_signed_area(&coords) > 0) != ccw {
+        coords.reverse();
+    }
+    coords
+}
+
+/// Serializes polygonized regions to a GeoJSON `FeatureCollection`
+///
+/// Each entry of `polygons` becomes one feature with a `MultiPolygon` geometry and a `label`
+/// property holding the map key.  Features are ordered by label.  Rings are closed if they
+/// are not already, and wound to the right hand rule of RFC 7946: exteriors
+/// counter-clockwise and holes clockwise.  Coordinates are written as integers.
+///
+/// Requires the `geojson` feature.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString, MultiPolygon, Polygon};
+/// use std::collections::HashMap;
+///
+/// let square = LineString::from(vec![
+///     Coord { x: 0, y: 0 },
+///     Coord { x: 0, y: 1 },
+///     Coord { x: 1, y: 1 },
+///     Coord { x: 1, y: 0 },
+/// ]);
+/// let polygons = HashMap::from([(7, MultiPolygon(vec![Polygon::new(square, vec![])]))]);
+/// assert_eq!(
+///     geospatial::to_geojson(&polygons),
+///     concat!(
+///         r#"{"type":"FeatureCollection","features":[{"type":"Feature","#,
+///         r#""properties":{"label":7},"geometry":{"type":"MultiPolygon","#,
+///         r#""coordinates":[[[[0,0],[1,0],[1,1],[0,1],[0,0]]]]}}]}"#,
+///     )
+/// );
+/// ```
+#[cfg(feature = "geojson")]
+pub fn to_geojson(polygons: &HashMap<i32, MultiPolygon<usize>>) -> String {
+    fn ring_json(coords: &[Coord<usize>]) -> String {
+        let pts: Vec<String> = coords
+            .iter()
+            .map(|c| format!("[{},{}]", c.x, c.y))
+            .collect();
+        format!("[{}]", pts.join(","))
+    }
+
+    let mut labels: Vec<&i32> = polygons.keys().collect();
+    labels.sort();
+
+    let features: Vec<String> = labels
+        .into_iter()
+        .map(|label| {
+            let polys: Vec<String> = polygons[label]
+                .0
+                .iter()
+                .map(|poly| {
+                    let mut rings = vec![ring_json(&wound_ring(poly.exterior(), true))];
+                    rings.extend(
+                        poly.interiors()
+                            .iter()
+                            .map(|hole| ring_json(&wound_ring(hole, false))),
+                    );
+                    format!("[{}]", rings.join(","))
+                })
+                .collect();
+            format!(
+                r#"{{"type":"Feature","properties":{{"label":{}}},"geometry":{{"type":"MultiPolygon","coordinates":[{}]}}}}"#,
+                label,
+                polys.join(",")
+            )
+        })
+        .collect();
+
+    format!(
+        r#"{{"type":"FeatureCollection","features":[{}]}}"#,
+        features.join(",")
+    )
+}
+
+/// Serializes the polygons of a `MultiPolygon<f64>` to a GeoJSON `FeatureCollection`
+///
+/// For polygons that have been through [`Affine`] or some other transform into world
+/// coordinates, where [`to_geojson`] only takes grid corners.  Each polygon becomes its own
+/// feature with a `Polygon` geometry, in order.  Rings are closed if they are not already,
+/// and wound to the right hand rule of RFC 7946: exteriors counter-clockwise and holes
+/// clockwise, with `y` north.
+///
+/// Requires the `geojson` feature.
+///
+/// # Parameters
+///
+/// - `mp`: The polygons.
+/// - `label`: If given, a `label` property put on every feature.
+///
+/// # Notes
+///
+/// - Coordinates are written in full, as the shortest decimal that reads back as the same
+///   `f64`.  `NaN` and infinite coordinates have no JSON form and make invalid output.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{LineString, MultiPolygon, Polygon};
+///
+/// // clockwise, so it gets turned round
+/// let square = LineString::from(vec![(0.5, 0.0), (0.5, 1.0), (1.5, 1.0), (1.5, 0.0)]);
+/// let mp = MultiPolygon(vec![Polygon::new(square, vec![])]);
+/// let json = geospatial::multipolygon_to_geojson(&mp, Some(3));
+/// assert_eq!(
+///     json,
+///     concat!(
+///         r#"{"type":"FeatureCollection","features":[{"type":"Feature","#,
+///         r#""properties":{"label":3},"geometry":{"type":"Polygon","#,
+///         r#""coordinates":[[[0.5,0],[1.5,0],[1.5,1],[0.5,1],[0.5,0]]]}}]}"#,
+///     )
+/// );
+///
+/// // and it reads back
+/// let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+/// assert_eq!(v["features"][0]["geometry"]["coordinates"][0][1][0], 1.5);
+/// let v: serde_json::Value =
+///     serde_json::from_str(&geospatial::multipolygon_to_geojson(&mp, None)).unwrap();
+/// assert_eq!(v["features"][0]["properties"], serde_json::json!({}));
+/// ```
+#[cfg(feature = "geojson")]
+pub fn multipolygon_to_geojson(mp: &MultiPolygon<f64>, label: Option<i64>) -> String {
+    fn ring_json(ring: &LineString<f64>, ccw: bool) -> String {
+        let mut coords = ring.0.clone();
+        if coords.first() != coords.last() {
+            coords.extend(coords.first().copied());
+        }
+        let area: f64 = coords
+            .windows(2)
+            .map(|w| w[0].x * w[1].y - w[1].x * w[0].y)
+            .sum();
+        if (area > 0.0) != ccw {
+            coords.reverse();
+        }
+        let pts: Vec<String> = coords
+            .iter()
+            .map(|c| format!("[{},{}]", c.x, c.y))
+            .collect();
+        format!("[{}]", pts.join(","))
+    }
+
+    let properties = match label {
+        Some(label) => format!(r#"{{"label":{label}}}"#),
+        None => "{}".to_string(),
+    };
+    let features: Vec<String> = mp
+        .0
+        .iter()
+        .map(|poly| {
+            let mut rings = vec![ring_json(poly.exterior(), true)];
+            rings.extend(poly.interiors().iter().map(|hole| ring_json(hole, false)));
+            format!(
+                r#"{{"type":"Feature","properties":{},"geometry":{{"type":"Polygon","coordinates":[{}]}}}}"#,
+                properties,
+                rings.join(",")
+            )
+        })
+        .collect();
+
+    format!(
+        r#"{{"type":"FeatureCollection","features":[{}]}}"#,
+        features.join(",")
+    )
+}
+
+// coordinates of a linestring in WKT form, eg (0 0, 1 0)
+fn wkt_coords(ls: &LineString<usize>) -> String {
+    let pts: Vec<String> = ls.0.iter().map(|c| format!("{} {}", c.x, c.y)).collect();
+    format!("({})", pts.join(", "))
+}
+
+/// Writes a `MultiLineString` as WKT
+///
+/// Coordinates are written as integers.  An empty `MultiLineString` gives
+/// `MULTILINESTRING EMPTY`.
+///
+/// # Examples
+///
+/// ```
+/// use geo::MultiLineString;
+/// use ndarray::array;
+///
+/// let grid = array![[0]];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(0, &e[&0], &grid);
+/// assert_eq!(
+///     geospatial::to_wkt_mls(&mls),
+///     "MULTILINESTRING ((0 0, 1 0, 1 1, 0 1, 0 0))"
+/// );
+/// assert_eq!(
+///     geospatial::to_wkt_mls(&MultiLineString::new(vec![])),
+///     "MULTILINESTRING EMPTY"
+/// );
+/// ```
+pub fn to_wkt_mls(mls: &MultiLineString<usize>) -> String {
+    if mls.0.is_empty() {
+        return "MULTILINESTRING EMPTY".to_string();
+    }
+    let lines: Vec<String> = mls.0.iter().map(wkt_coords).collect();
+    format!("MULTILINESTRING ({})", lines.join(", "))
+}
+
+/// Writes a `Polygon` as WKT
+///
+/// The exterior is written first followed by any holes.  Coordinates are written as
+/// integers.  A polygon with an empty exterior gives `POLYGON EMPTY`.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString, Polygon};
+///
+/// let poly = Polygon::new(
+///     LineString::from(vec![
+///         Coord { x: 0, y: 0 },
+///         Coord { x: 3, y: 0 },
+///         Coord { x: 3, y: 3 },
+///         Coord { x: 0, y: 3 },
+///     ]),
+///     vec![LineString::from(vec![
+///         Coord { x: 1, y: 1 },
+///         Coord { x: 1, y: 2 },
+///         Coord { x: 2, y: 2 },
+///         Coord { x: 2, y: 1 },
+///     ])],
+/// );
+/// assert_eq!(
+///     geospatial::to_wkt_polygon(&poly),
+///     "POLYGON ((0 0, 3 0, 3 3, 0 3, 0 0), (1 1, 1 2, 2 2, 2 1, 1 1))"
+/// );
+/// ```
+pub fn to_wkt_polygon(poly: &geo::Polygon<usize>) -> String {
+    if poly.exterior().0.is_empty() {
+        return "POLYGON EMPTY".to_string();
+    }
+    let mut rings = vec![wkt_coords(poly.exterior())];
+    rings.extend(poly.interiors().iter().map(wkt_coords));
+    format!("POLYGON ({})", rings.join(", "))
+}
+
+/// Writes a `MultiLineString` as WKT, the same as [`to_wkt_mls`]
+///
+/// Coordinates are written as integers, and an empty `MultiLineString` gives
+/// `MULTILINESTRING EMPTY`.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{LineString, MultiLineString};
+///
+/// let mls = MultiLineString::new(vec![
+///     LineString::from(vec![(0, 0), (2, 0), (2, 1), (0, 1), (0, 0)]),
+///     LineString::from(vec![(5, 5), (6, 5), (6, 6), (5, 5)]),
+/// ]);
+/// assert_eq!(
+///     geospatial::multilinestring_to_wkt(&mls),
+///     "MULTILINESTRING ((0 0, 2 0, 2 1, 0 1, 0 0), (5 5, 6 5, 6 6, 5 5))"
+/// );
+/// assert_eq!(
+///     geospatial::multilinestring_to_wkt(&MultiLineString::new(vec![])),
+///     "MULTILINESTRING EMPTY"
+/// );
+/// ```
+pub fn multilinestring_to_wkt(mls: &MultiLineString<usize>) -> String {
+    to_wkt_mls(mls)
+}
+
+/// Writes a `Polygon` as WKT, the same as [`to_wkt_polygon`]
+///
+/// # Examples
+///
+/// ```
+/// use geo::{LineString, Polygon};
+///
+/// let poly = Polygon::new(LineString::from(vec![(0, 0), (1, 0), (1, 1), (0, 1)]), vec![]);
+/// assert_eq!(geospatial::polygon_to_wkt(&poly), "POLYGON ((0 0, 1 0, 1 1, 0 1, 0 0))");
+/// let empty = Polygon::new(LineString::<usize>::new(vec![]), vec![]);
+/// assert_eq!(geospatial::polygon_to_wkt(&empty), "POLYGON EMPTY");
+/// ```
+pub fn polygon_to_wkt(poly: &geo::Polygon<usize>) -> String {
+    to_wkt_polygon(poly)
+}
+
+/// A boundary edge as a newtype, convertible to and from a [`GridEdge`].
+///
+/// With the `serde` feature it serializes as the four element array `[x1, y1, x2, y2]`,
+/// which makes caching the output of [`marching_squares`] compact.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "serde")] {
+/// use geo::Coord;
+/// use geospatial::Edge;
+/// use ndarray::array;
+/// use std::collections::HashMap;
+///
+/// let grid = array![[1, 2]];
+/// let edges: HashMap<i32, Vec<Edge>> = geospatial::marching_squares(&grid)
+///     .into_iter()
+///     .map(|(k, v)| (k, v.into_iter().map(Edge::from).collect()))
+///     .collect();
+///
+/// let e = Edge(Coord { x: 1, y: 0 }, Coord { x: 1, y: 1 });
+/// assert_eq!(serde_json::to_string(&e).unwrap(), "[1,0,1,1]");
+///
+/// let json = serde_json::to_string(&edges).unwrap();
+/// let back: HashMap<i32, Vec<Edge>> = serde_json::from_str(&json).unwrap();
+/// assert_eq!(back, edges);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge(pub Coord<usize>, pub Coord<usize>);
+
+impl From<GridEdge> for Edge {
+    fn from((a, b): GridEdge) -> Self {
+        Edge(a, b)
+    }
+}
+
+impl From<Edge> for GridEdge {
+    fn from(e: Edge) -> Self {
+        (e.0, e.1)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Edge {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        [self.0.x, self.0.y, self.1.x, self.1.y].serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Edge {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [x1, y1, x2, y2] = <[usize; 4]>::deserialize(deserializer)?;
+        Ok(Edge(Coord { x: x1, y: y1 }, Coord { x: x2, y: y2 }))
+    }
+}
+
+// stroke colours cycled through for successive rings in the svg output
+const SVG_PALETTE: [&str; 6] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#008080",
+];
+
+// wraps svg elements in a document big enough to show every coordinate up to (maxx, maxy)
+fn svg_document(body: &str, maxx: usize, maxy: usize, cell_px: f64) -> String {
+    let (w, h) = (maxx as f64 * cell_px, maxy as f64 * cell_px);
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n{body}</svg>\n"
+    )
+}
+
+/// Draws a list of edges as a standalone SVG document
+///
+/// Handy for debugging the output of [`marching_squares`].  Every edge becomes a `<line>`,
+/// with grid coordinates scaled by `cell_px`.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![[1, 2]];
+/// let e = geospatial::marching_squares(&grid);
+/// assert_eq!(
+///     geospatial::edges_to_svg(&e[&1][..2], 10.0),
+///     concat!(
+///         "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\" height=\"10\" viewBox=\"0 0 10 10\">\n",
+///         "<line x1=\"0\" y1=\"0\" x2=\"10\" y2=\"0\" stroke=\"black\" />\n",
+///         "<line x1=\"0\" y1=\"10\" x2=\"10\" y2=\"10\" stroke=\"black\" />\n",
+///         "</svg>\n",
+///     )
+/// );
+/// ```
+pub fn edges_to_svg(edges: &[GridEdge], cell_px: f64) -> String {
+    let mut body = String::new();
+    let (mut maxx, mut maxy) = (0, 0);
+    for (a, b) in edges {
+        maxx = maxx.max(a.x).max(b.x);
+        maxy = maxy.max(a.y).max(b.y);
+        body.push_str(&format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" />\n",
+            a.x as f64 * cell_px,
+            a.y as f64 * cell_px,
+            b.x as f64 * cell_px,
+            b.y as f64 * cell_px,
+        ));
+    }
+    svg_document(&body, maxx, maxy, cell_px)
+}
+
+/// Draws every ring of a `MultiLineString` as a standalone SVG document
+///
+/// Each `LineString` becomes a `<polyline>` with grid coordinates scaled by `cell_px`.
+/// Successive rings cycle through a small palette of stroke colours so touching rings can
+/// be told apart.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![[0]];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(0, &e[&0], &grid);
+/// assert_eq!(
+///     geospatial::mls_to_svg(&mls, 2.5),
+///     concat!(
+///         "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"2.5\" height=\"2.5\" viewBox=\"0 0 2.5 2.5\">\n",
+///         "<polyline points=\"0,0 2.5,0 2.5,2.5 0,2.5 0,0\" fill=\"none\" stroke=\"#e6194b\" />\n",
+///         "</svg>\n",
+///     )
+/// );
+/// ```
+pub fn mls_to_svg(mls: &MultiLineString<usize>, cell_px: f64) -> String {
+    let mut body = String::new();
+    let (mut maxx, mut maxy) = (0, 0);
+    for (i, ls) in mls.0.iter().enumerate() {
+        let pts: Vec<String> =
+            ls.0.iter()
+                .map(|c| {
+                    maxx = maxx.max(c.x);
+                    maxy = maxy.max(c.y);
+                    format!("{},{}", c.x as f64 * cell_px, c.y as f64 * cell_px)
+                })
+                .collect();
+        body.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" />\n",
+            pts.join(" "),
+            SVG_PALETTE[i % SVG_PALETTE.len()],
+        ));
+    }
+    svg_document(&body, maxx, maxy, cell_px)
+}
+
+// all the edges that row r is responsible for: its part of the grid border, the edges
+// between it and its right neighbours, and the edges between it and the row below
+#[cfg(feature = "rayon")]
+fn row_edges<T>(grid: &Array2<T>, r: usize, ret: &mut LabelMap<T, Vec<GridEdge>>)
+where
+    T: Eq + Hash + Copy,
+{
+    let (nrows, ncols) = grid.dim();
+    if ncols == 0 {
+        return;
+    }
+    for c in 0..ncols {
+        let me = grid[[r, c]];
+        if r == 0 {
+            let edge = (Coord { x: c, y: r }, Coord { x: c + 1, y: r });
+            ret.entry(me).or_default().push(edge);
+        }
+        if r == nrows - 1 {
+            let edge = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
+            ret.entry(me).or_default().push(edge);
+        }
+        if c == 0 {
+            let edge = (Coord { x: c, y: r }, Coord { x: c, y: r + 1 });
+            ret.entry(me).or_default().push(edge);
+        }
+        if c == ncols - 1 {
+            let edge = (Coord { x: c + 1, y: r }, Coord { x: c + 1, y: r + 1 });
+            ret.entry(me).or_default().push(edge);
+        } else {
+            let right = grid[[r, c + 1]];
+            if me != right {
+                let edge = (Coord { x: c + 1, y: r }, Coord { x: c + 1, y: r + 1 });
+                ret.entry(me).or_default().push(edge);
+                ret.entry(right).or_default().push(edge);
+            }
+        }
+        if r != nrows - 1 {
+            let down = grid[[r + 1, c]];
+            if me != down {
+                let edge = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
+                ret.entry(me).or_default().push(edge);
+                ret.entry(down).or_default().push(edge);
+            }
+        }
+    }
+}
+
+/// Marching squares, spread over threads with rayon
+///
+/// Produces the same edges as [`marching_squares`], but the rows of the grid are split into
+/// chunks that are scanned in parallel, each into its own `HashMap`, and the maps are then
+/// merged into one sized to fit, so merging doesn't rehash.
+///
+/// Requires the `rayon` feature.
+///
+/// # Notes
+///
+/// - The set of edges for each region is identical to [`marching_squares`], but the order
+///   the edges appear in is not.  The order is deterministic from run to run.
+/// - [`edges_to_multilinestring`] does not depend on edge order to assemble correct rings,
+///   but the ring each one starts from, and so the order of the rings, may differ from
+///   using the serial version.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+/// use std::collections::HashSet;
+///
+/// let grid = array![
+///     [4, 1, 1, 2],
+///     [1, 1, 2, 3],
+///     [1, 2, 2, 2],
+/// ];
+/// let serial = geospatial::marching_squares(&grid);
+/// let par = geospatial::marching_squares_par(&grid);
+/// assert_eq!(serial.len(), par.len());
+/// for (k, v) in &serial {
+///     assert_eq!(v.len(), par[k].len());
+///     assert_eq!(
+///         v.iter().collect::<HashSet<_>>(),
+///         par[k].iter().collect::<HashSet<_>>()
+///     );
+/// }
+/// ```
+#[cfg(feature = "rayon")]
+pub fn marching_squares_par<T>(grid: &Array2<T>) -> LabelMap<T, Vec<GridEdge>>
+where
+    T: Eq + Hash + Copy + Send + Sync,
+{
+    use rayon::prelude::*;
+
+    let nrows = grid.nrows();
+    let chunk = nrows.div_ceil(4 * rayon::current_num_threads()).max(1);
+    let parts: Vec<LabelMap<T, Vec<GridEdge>>> = (0..nrows.div_ceil(chunk))
+        .into_par_iter()
+        .map(|i| {
+            let mut part = LabelMap::default();
+            for r in i * chunk..((i + 1) * chunk).min(nrows) {
+                row_edges(grid, r, &mut part);
+            }
+            part
+        })
+        .collect();
+
+    // size everything up front so the merge never grows a vec or rehashes the map
+    let mut counts: LabelMap<T, usize> = LabelMap::default();
+    for part in &parts {
+        for (k, v) in part {
+            *counts.entry(*k).or_default() += v.len();
+        }
+    }
+    let mut ret: LabelMap<T, Vec<GridEdge>> = LabelMap::default();
+    ret.reserve(counts.len());
+    for (k, n) in counts {
+        ret.insert(k, Vec::with_capacity(n));
+    }
+
+    // merge in chunk order so the output is the same every run
+    for part in parts {
+        for (k, v) in part {
+            ret.get_mut(&k).unwrap().extend(v);
+        }
+    }
+    ret
+}
+
+/// Polygonize every region, assembling the labels in parallel with rayon
+///
+/// Runs [`marching_squares`] once, then each label's edges are assembled into polygons
+/// with [`region_to_multipolygon`] on their own thread.  Labels are independent so this
+/// scales with the number of labels, it does not help a grid with only a couple of huge
+/// regions.
+///
+/// Requires the `rayon` feature.
+///
+/// # Returns
+///
+/// A `HashMap` from each label to its polygons.  Exteriors are wound counter-clockwise
+/// and holes clockwise, by signed area.
+///
+/// # Notes
+///
+/// - The regions are the same as from [`trace_regions`], with the same area, but the
+///   rings may start at different corners and come in a different order.
+/// - Unlike [`trace_regions`], a hole that touches the exterior at a corner stays a hole,
+///   joined to the exterior at that corner.
+///
+/// # Examples
+///
+/// ```
+/// use geospatial::polygon_area;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 1, 2],
+///     [1, 0, 1, 2],
+///     [1, 1, 1, 2],
+/// ];
+/// let polys = geospatial::polygonize_par(&grid);
+/// assert_eq!(polys.len(), 3);
+/// let p = &polys[&1].0[0];
+/// assert_eq!(polys[&1].0.len(), 1);
+/// assert_eq!(polygon_area(p.exterior()), 9);
+/// assert_eq!(polygon_area(&p.interiors()[0]), -1);
+/// assert_eq!(polys[&2].0[0].exterior().0.len(), 9);
+/// ```
+#[cfg(feature = "rayon")]
+pub fn polygonize_par<T>(grid: &Array2<T>) -> HashMap<T, MultiPolygon<usize>>
+where
+    T: Eq + Hash + Copy + Send + Sync,
+{
+    use rayon::prelude::*;
+
+    let edges: Vec<(T, Vec<GridEdge>)> = marching_squares(grid).into_iter().collect();
+    edges
+        .into_par_iter()
+        .map(|(id, e)| (id, region_to_multipolygon(id, &e, grid)))
+        .collect()
+}
+
+/// Number of cells with each label
+///
+/// A single pass over the grid, much cheaper than polygonizing when all that is needed is
+/// the size of each region, or a histogram of the labels.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+/// use std::collections::HashMap;
+///
+/// let grid = array![
+///     [1, 1, 2],
+///     [1, 3, 2],
+/// ];
+/// assert_eq!(
+///     geospatial::region_areas(&grid),
+///     HashMap::from([(1, 3), (2, 2), (3, 1)])
+/// );
+/// ```
+pub fn region_areas<T>(grid: &Array2<T>) -> HashMap<T, usize>
+where
+    T: Eq + Hash + Copy,
+{
+    let mut ret: HashMap<T, usize> = HashMap::new();
+    for v in grid {
+        *ret.entry(*v).or_default() += 1;
+    }
+    ret
+}
+
+/// How many cells have each label, the same as [`region_areas`]
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![[7, 7, 9]];
+/// let h = geospatial::label_histogram(&grid);
+/// assert_eq!(h[&7], 2);
+/// assert_eq!(h, geospatial::region_areas(&grid));
+/// ```
+pub fn label_histogram<T>(grid: &Array2<T>) -> HashMap<T, usize>
+where
+    T: Eq + Hash + Copy,
+{
+    region_areas(grid)
+}
+
+/// The `n` labels with the most cells
+///
+/// The top of the histogram [`region_areas`] gives, for deciding which classes are worth
+/// polygonizing.
+///
+/// # Returns
+///
+/// Up to `n` `(label, cells)` pairs, most cells first.  Labels with the same count are in
+/// the order they first appear in the grid, row by row.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 2, 4],
+///     [1, 3, 2, 4],
+/// ];
+/// assert_eq!(geospatial::most_common(&grid, 2), vec![(1, 3), (2, 2)]);
+/// assert_eq!(geospatial::most_common(&grid, 10).len(), 4);
+/// ```
+pub fn most_common<T>(grid: &Array2<T>, n: usize) -> Vec<(T, usize)>
+where
+    T: Eq + Hash + Copy,
+{
+    let mut counts: Vec<(T, usize)> = Vec::new();
+    let mut slot: HashMap<T, usize> = HashMap::new();
+    for v in grid {
+        let k = *slot.entry(*v).or_insert_with(|| {
+            counts.push((*v, 0));
+            counts.len() - 1
+        });
+        counts[k].1 += 1;
+    }
+    counts.sort_by_key(|c| std::cmp::Reverse(c.1));
+    counts.truncate(n);
+    counts
+}
+
+/// The size of one region, from [`region_stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RegionStats {
+    /// how many cells have the label
+    pub area_cells: usize,
+    /// how many unit edges are on the region's boundary, including along the grid border
+    pub perimeter_edges: usize,
+}
+
+/// Area and perimeter of every region, without assembling any polygons
+///
+/// The perimeter is the number of edges [`marching_squares`] would give the region, so a
+/// summary table of sizes or compactness doesn't need the edges themselves.  The grid is
+/// scanned the same way as [`marching_squares`] does, but the edges are only counted.
+///
+/// # Examples
+///
+/// ```
+/// use geospatial::RegionStats;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 2],
+///     [1, 1, 2],
+/// ];
+/// let stats = geospatial::region_stats(&grid);
+/// assert_eq!(stats[&1], RegionStats { area_cells: 4, perimeter_edges: 8 });
+/// assert_eq!(stats[&2], RegionStats { area_cells: 2, perimeter_edges: 6 });
+/// assert_eq!(stats[&1].perimeter_edges, geospatial::marching_squares(&grid)[&1].len());
+/// ```
+pub fn region_stats<T>(grid: &Array2<T>) -> HashMap<T, RegionStats>
+where
+    T: Eq + Hash + Copy,
+{
+    let mut ret: HashMap<T, RegionStats> = HashMap::new();
+    for v in grid {
+        ret.entry(*v).or_default().area_cells += 1;
+    }
+    marching_squares_scan(&grid.view(), |label, _| {
+        if let Some(s) = ret.get_mut(label) {
+            s.perimeter_edges += 1;
+        }
+    });
+    ret
+}
+
+/// Signed area of a ring by the shoelace formula
+///
+/// The sum is done in `i64` (or wider) so the sign is right even though the coordinates are
+/// `usize`.  The area is positive when the ring is counter-clockwise with `y` increasing
+/// upwards, ie clockwise when drawn with rows increasing downwards.  The ring does not need
+/// to repeat its first point at the end.
+///
+/// Rings from [`edges_to_multilinestring`] only have horizontal and vertical edges so their
+/// area is always a whole number of cells, for other rings any half is truncated.
+///
+/// # Panics
+///
+/// If the area does not fit in an `i64`, use [`ring_signed_area`] for rings that big.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1],
+///     [1, 0],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(1, &e[&1], &grid);
+/// assert_eq!(geospatial::polygon_area(&mls.0[0]).abs(), 3);
+///
+/// let ccw = LineString::from(vec![
+///     Coord { x: 0, y: 0 },
+///     Coord { x: 2, y: 0 },
+///     Coord { x: 2, y: 2 },
+///     Coord { x: 0, y: 2 },
+/// ]);
+/// assert_eq!(geospatial::polygon_area(&ccw), 4);
+/// let mut cw = ccw.clone();
+/// cw.0.reverse();
+/// assert_eq!(geospatial::polygon_area(&cw), -4);
+/// ```
+pub fn polygon_area(ring: &LineString<usize>) -> i64 {
+    let mut coords = ring.0.clone();
+    if coords.first() != coords.last() {
+        coords.push(coords[0]);
+    }
+    i64::try_from(twice_signed_area(&coords) / 2).expect("polygon area does not fit in an i64")
+}
+
+/// [`polygon_area`] in `i128`, for rings too big for `i64`
+///
+/// The shoelace sum is in `i128` throughout, so a ring anywhere on a `usize` lattice gets
+/// its exact area, where [`polygon_area`] or casting to `geo`'s signed types can overflow.
+/// Positive means counter-clockwise, and the ring needn't be closed.  As with
+/// [`polygon_area`] rings with diagonal edges can have half a cell, which is truncated.
+///
+/// # Examples
+///
+/// ```
+/// use geo::LineString;
+///
+/// // 5 billion square, 2.5e19 cells, more than an i64 holds
+/// let n = 5_000_000_000usize;
+/// let ring = LineString::from(vec![(0, 0), (n, 0), (n, n), (0, n), (0, 0)]);
+/// assert_eq!(geospatial::ring_signed_area(&ring), 25_000_000_000_000_000_000);
+/// assert!(geospatial::ring_signed_area(&ring) > i64::MAX as i128);
+/// ```
+pub fn ring_signed_area(ring: &LineString<usize>) -> i128 {
+    let mut coords = ring.0.clone();
+    if coords.first() != coords.last() {
+        coords.extend(coords.first().copied());
+    }
+    twice_signed_area(&coords) / 2
+}
+
+/// Whether a ring is wound counter-clockwise, as outer rings from
+/// [`edges_to_multilinestring`] are
+///
+/// Decided exactly from the sign of the shoelace sum in `i128`, so it is never thrown by
+/// rounding however big the ring.  A ring with no area, or fewer than 3 points, isn't
+/// counter-clockwise.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 1],
+///     [1, 0, 1],
+///     [1, 1, 1],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(1, &e[&1], &grid);
+/// let ccw: Vec<bool> = mls.0.iter().map(geospatial::ring_is_ccw).collect();
+/// // one outer ring and one hole
+/// assert_eq!(ccw.iter().filter(|c| **c).count(), 1);
+/// assert_eq!(ccw.len(), 2);
+/// ```
+pub fn ring_is_ccw(ring: &LineString<usize>) -> bool {
+    let mut coords = ring.0.clone();
+    if coords.first() != coords.last() {
+        coords.extend(coords.first().copied());
+    }
+    twice_signed_area(&coords) > 0
+}
+
+// splits a grid into 4-connected components of equal value, returning the component of
+// every cell along with each component's value
+fn value_components<T>(grid: &Array2<T>) -> (Array2<usize>, Vec<T>)
+where
+    T: Eq + Copy,
+{
+    let (nrows, ncols) = grid.dim();
+    let mut comp: Array2<usize> = Array2::from_elem((nrows, ncols), usize::MAX);
+    let mut values: Vec<T> = Vec::new();
+    for r in 0..nrows {
+        for c in 0..ncols {
+            if comp[[r, c]] != usize::MAX {
+                continue;
+            }
+            for cell in flood_fill(grid, Coord { x: c, y: r }, Connectivity::Four) {
+                comp[[cell.y, cell.x]] = values.len();
+            }
+            values.push(grid[[r, c]]);
+        }
+    }
+    (comp, values)
+}
+
+/// Sieve filter, removing regions smaller than a minimum size
+///
+/// Every 4-connected region of fewer than `min_size` cells is merged into its largest
+/// neighbouring region, taking on that region's label, much like GDAL's sieve.  Regions are
+/// merged smallest first and sizes include anything already merged, so a cluster of small
+/// regions can build up into one big enough to keep.
+///
+/// # Parameters
+///
+/// - `grid`: A 2D array of values representing labeled regions.
+/// - `min_size`: regions with fewer cells than this are removed
+///
+/// # Returns
+///
+/// A copy of `grid` with the small regions relabelled.
+///
+/// # Notes
+///
+/// - When two neighbours are equally large the one with the smaller label wins, so the
+///   output is reproducible.
+/// - A region with no neighbours (the whole grid) is kept whatever its size.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 1, 2, 2],
+///     [1, 5, 1, 2, 2],
+///     [1, 1, 3, 2, 2],
+/// ];
+/// assert_eq!(
+///     geospatial::sieve(&grid, 2),
+///     array![
+///         [1, 1, 1, 2, 2],
+///         [1, 1, 1, 2, 2],
+///         [1, 1, 1, 2, 2],
+///     ]
+/// );
+///
+/// // equally large neighbours, the smaller label wins
+/// let grid = array![[2, 2, 5, 1, 1]];
+/// assert_eq!(geospatial::sieve(&grid, 2), array![[2, 2, 1, 1, 1]]);
+/// ```
+pub fn sieve<T>(grid: &Array2<T>, min_size: usize) -> Array2<T>
+where
+    T: Eq + Hash + Copy + Ord,
+{
+    fn find(parent: &mut [usize], mut x: usize) -> usize {
+        while parent[x] != x {
+            parent[x] = parent[parent[x]];
+            x = parent[x];
+        }
+        x
+    }
+
+    let (comp, values) = value_components(grid);
+    let n = values.len();
+    let (nrows, ncols) = grid.dim();
+
+    let mut size: Vec<usize> = vec![0; n];
+    for &id in &comp {
+        size[id] += 1;
+    }
+    let mut nbrs: Vec<HashSet<usize>> = vec![HashSet::new(); n];
+    for r in 0..nrows {
+        for c in 0..ncols {
+            let me = comp[[r, c]];
+            if c + 1 < ncols && comp[[r, c + 1]] != me {
+                nbrs[me].insert(comp[[r, c + 1]]);
+                nbrs[comp[[r, c + 1]]].insert(me);
+            }
+            if r + 1 < nrows && comp[[r + 1, c]] != me {
+                nbrs[me].insert(comp[[r + 1, c]]);
+                nbrs[comp[[r + 1, c]]].insert(me);
+            }
+        }
+    }
+
+    // merge smallest first, each into its current largest neighbour
+    let mut parent: Vec<usize> = (0..n).collect();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by_key(|&id| (size[id], id));
+    for id in order {
+        if find(&mut parent, id) != id || size[id] >= min_size {
+            continue;
+        }
+        let candidates: Vec<usize> = nbrs[id]
+            .clone()
+            .into_iter()
+            .map(|nb| find(&mut parent, nb))
+            .filter(|&nb| nb != id)
+            .collect();
+        let best = candidates
+            .into_iter()
+            .min_by_key(|&nb| (std::cmp::Reverse(size[nb]), values[nb], nb));
+        if let Some(target) = best {
+            parent[id] = target;
+            size[target] += size[id];
+            let mine = std::mem::take(&mut nbrs[id]);
+            nbrs[target].extend(mine);
+        }
+    }
+
+    comp.mapv(|id| values[find(&mut parent, id)])
+}
+
+/// Adds vertices to a `LineString` so no segment is longer than `max_segment`
+///
+/// Each segment longer than `max_segment` is split into the fewest equal pieces that are
+/// short enough, using plain Euclidean length.  The original vertices are all kept, so a
+/// transform followed by [`rasterize_linestring`] follows the intended path more closely.
+///
+/// # Notes
+///
+/// - Zero length segments are passed through unchanged, and so are lines of fewer than two
+///   points.
+/// - The last vertex is kept as it is too, so a closed ring stays closed.
+/// - If `max_segment` is not positive the `LineString` is returned unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let ls = LineString::from(vec![
+///     Coord { x: 0.0, y: 0.0 },
+///     Coord { x: 3.0, y: 0.0 },
+///     Coord { x: 3.0, y: 0.0 },
+///     Coord { x: 3.0, y: 1.0 },
+/// ]);
+/// assert_eq!(
+///     geospatial::densify(&ls, 1.5),
+///     LineString::from(vec![
+///         Coord { x: 0.0, y: 0.0 },
+///         Coord { x: 1.5, y: 0.0 },
+///         Coord { x: 3.0, y: 0.0 },
+///         Coord { x: 3.0, y: 0.0 },
+///         Coord { x: 3.0, y: 1.0 },
+///     ])
+/// );
+///
+/// let ring = LineString::from(vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 0.0)]);
+/// let dense = geospatial::densify(&ring, 0.5);
+/// assert!(dense.is_closed());
+/// assert!(dense.lines().all(|l| (l.end.x - l.start.x).hypot(l.end.y - l.start.y) <= 0.5));
+/// let point = LineString::from(vec![(1.0, 1.0)]);
+/// assert_eq!(geospatial::densify(&point, 0.5), point);
+/// ```
+pub fn densify(ls: &LineString<f64>, max_segment: f64) -> LineString<f64> {
+    if max_segment <= 0.0 || ls.0.len() < 2 {
+        return ls.clone();
+    }
+    let mut out = vec![ls.0[0]];
+    for w in ls.0.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let len = (b.x - a.x).hypot(b.y - a.y);
+        let pieces = (len / max_segment).ceil().max(1.0) as usize;
+        for i in 1..pieces {
+            let t = i as f64 / pieces as f64;
+            out.push(Coord {
+                x: a.x + t * (b.x - a.x),
+                y: a.y + t * (b.y - a.y),
+            });
+        }
+        out.push(b);
+    }
+    LineString::new(out)
+}
+
+/// The parts of a `LineString` inside a polygon
+///
+/// Every segment is cut where it crosses the polygon's exterior or holes, and the pieces
+/// inside kept.  Pieces that follow on from each other are joined, so each time the line
+/// enters the polygon it gives one `LineString` in the result, in order along the line.
+/// Feed the result to a transform and [`rasterize_linestring`] to only burn the part of a
+/// route inside a study area.
+///
+/// # Notes
+///
+/// - The polygon's boundary counts as inside, so a line running along the edge is kept.
+/// - Pieces with no length, where the line just touches the boundary, are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString, Polygon};
+///
+/// let square = Polygon::new(
+///     LineString::from(vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0), (0.0, 0.0)]),
+///     vec![LineString::from(vec![(1.0, 1.0), (3.0, 1.0), (3.0, 3.0), (1.0, 3.0), (1.0, 1.0)])],
+/// );
+/// let ls = LineString::from(vec![(-1.0, 2.0), (5.0, 2.0)]);
+/// let clipped = geospatial::clip_linestring_to_polygon(&ls, &square);
+/// assert_eq!(clipped.0, vec![
+///     LineString::from(vec![(0.0, 2.0), (1.0, 2.0)]),
+///     LineString::from(vec![(3.0, 2.0), (4.0, 2.0)]),
+/// ]);
+///
+/// // joined up across vertices
+/// let ls = LineString::from(vec![(-1.0, 0.5), (0.5, 0.5), (0.5, -1.0)]);
+/// let clipped = geospatial::clip_linestring_to_polygon(&ls, &square);
+/// assert_eq!(clipped.0, vec![
+///     LineString::from(vec![(0.0, 0.5), (0.5, 0.5), (0.5, 0.0)]),
+/// ]);
+/// ```
+pub fn clip_linestring_to_polygon(
+    ls: &LineString<f64>,
+    poly: &geo::Polygon<f64>,
+) -> MultiLineString<f64> {
+    use geo::Intersects;
+
+    let rings: Vec<&LineString<f64>> = std::iter::once(poly.exterior())
+        .chain(poly.interiors())
+        .collect();
+    let cross = |u: Coord<f64>, v: Coord<f64>| u.x * v.y - u.y * v.x;
+    let at = |p: Coord<f64>, q: Coord<f64>, t: f64| Coord {
+        x: p.x + t * (q.x - p.x),
+        y: p.y + t * (q.y - p.y),
+    };
+
+    let mut out: Vec<LineString<f64>> = Vec::new();
+    // the piece being built, if the line is inside at the end of the last segment
+    let mut current: Option<Vec<Coord<f64>>> = None;
+    for w in ls.0.windows(2) {
+        let (p, q) = (w[0], w[1]);
+        let d = q - p;
+        if p == q {
+            continue;
+        }
+
+        // where the segment crosses the boundary, as fractions along it
+        let mut ts = vec![0.0, 1.0];
+        for ring in &rings {
+            for e in ring.0.windows(2) {
+                let (a, b) = (e[0], e[1]);
+                let denom = cross(d, b - a);
+                if denom == 0.0 {
+                    continue;
+                }
+                let t = cross(a - p, b - a) / denom;
+                let u = cross(a - p, d) / denom;
+                if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+                    ts.push(t);
+                }
+            }
+        }
+        ts.sort_by(f64::total_cmp);
+        ts.dedup();
+
+        for t in ts.windows(2) {
+            let (t0, t1) = (t[0], t[1]);
+            if !poly.intersects(&at(p, q, (t0 + t1) / 2.0)) {
+                if let Some(line) = current.take() {
+                    out.push(LineString::new(line));
+                }
+                continue;
+            }
+            match current.as_mut() {
+                Some(line) if t0 == 0.0 => line.push(at(p, q, t1)),
+                _ => {
+                    if let Some(line) = current.take() {
+                        out.push(LineString::new(line));
+                    }
+                    current = Some(vec![at(p, q, t0), at(p, q, t1)]);
+                }
+            }
+        }
+    }
+    out.extend(current.map(LineString::new));
+    MultiLineString::new(out)
+}
+
+// distance from p to the segment a-b
+fn segment_distance(p: Coord<f64>, a: Coord<f64>, b: Coord<f64>) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 == 0.0 {
+        0.0
+    } else {
+        (((p.x - a.x) * dx + (p.y - a.y) * dy) / len2).clamp(0.0, 1.0)
+    };
+    (p.x - (a.x + t * dx)).hypot(p.y - (a.y + t * dy))
+}
+
+// douglas-peucker over pts[first..=last], setting keep for every interior point that stays
+fn dp_keep(pts: &[Coord<f64>], first: usize, last: usize, tolerance: f64, keep: &mut [bool]) {
+    let mut todo = vec![(first, last)];
+    while let Some((i, j)) = todo.pop() {
+        let far = (i + 1..j)
+            .map(|k| (k, segment_distance(pts[k], pts[i], pts[j])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((k, d)) = far
+            && d > tolerance
+        {
+            keep[k] = true;
+            todo.push((i, k));
+            todo.push((k, j));
+        }
+    }
+}
+
+/// Douglas-Peucker simplification of a `LineString`
+///
+/// Removes vertices so that the result never strays more than `tolerance` from the
+/// original.  The first and last vertices are always kept.  Unlike an exact collinear
+/// removal this trades fidelity for fewer vertices.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// let ls = LineString::from(vec![
+///     Coord { x: 0.0, y: 0.0 },
+///     Coord { x: 1.0, y: 0.1 },
+///     Coord { x: 2.0, y: -0.1 },
+///     Coord { x: 3.0, y: 5.0 },
+///     Coord { x: 4.0, y: 6.0 },
+/// ]);
+/// assert_eq!(
+///     geospatial::simplify_dp(&ls, 0.5),
+///     LineString::from(vec![
+///         Coord { x: 0.0, y: 0.0 },
+///         Coord { x: 2.0, y: -0.1 },
+///         Coord { x: 3.0, y: 5.0 },
+///         Coord { x: 4.0, y: 6.0 },
+///     ])
+/// );
+/// ```
+pub fn simplify_dp(ls: &LineString<f64>, tolerance: f64) -> LineString<f64> {
+    let n = ls.0.len();
+    if n < 3 {
+        return ls.clone();
+    }
+    let mut keep = vec![false; n];
+    keep[0] = true;
+    keep[n - 1] = true;
+    dp_keep(&ls.0, 0, n - 1, tolerance, &mut keep);
+    LineString::new(
+        ls.0.iter()
+            .zip(keep)
+            .filter_map(|(c, k)| k.then_some(*c))
+            .collect(),
+    )
+}
+
+/// Douglas-Peucker simplification of a closed ring
+///
+/// Like [`simplify_dp`], but the ring is split at the vertex farthest from its start so both
+/// halves simplify properly, and the result is always closed with at least 4 points (a
+/// triangle) so it is still usable as a polygon ring.  An open ring is closed first, and
+/// rings with fewer than 4 points are returned unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+///
+/// // a square with a tiny wobble on one side
+/// let ring = LineString::from(vec![
+///     Coord { x: 0.0, y: 0.0 },
+///     Coord { x: 0.0, y: 2.0 },
+///     Coord { x: 0.1, y: 4.0 },
+///     Coord { x: 4.0, y: 4.0 },
+///     Coord { x: 4.0, y: 0.0 },
+///     Coord { x: 0.0, y: 0.0 },
+/// ]);
+/// assert_eq!(
+///     geospatial::simplify_dp_ring(&ring, 0.5),
+///     LineString::from(vec![
+///         Coord { x: 0.0, y: 0.0 },
+///         Coord { x: 0.1, y: 4.0 },
+///         Coord { x: 4.0, y: 4.0 },
+///         Coord { x: 4.0, y: 0.0 },
+///         Coord { x: 0.0, y: 0.0 },
+///     ])
+/// );
+///
+/// // never collapses below a triangle
+/// assert_eq!(geospatial::simplify_dp_ring(&ring, 100.0).0.len(), 4);
+/// ```
+pub fn simplify_dp_ring(ring: &LineString<f64>, tolerance: f64) -> LineString<f64> {
+    let mut pts = ring.0.clone();
+    if pts.first() != pts.last() {
+        pts.push(pts[0]);
+    }
+    let n = pts.len();
+    if n < 4 {
+        return LineString::new(pts);
+    }
+
+    // split at the point farthest from the start
+    let d = |k: usize| (pts[k].x - pts[0].x).hypot(pts[k].y - pts[0].y);
+    let split = (1..n - 1).max_by(|&a, &b| d(a).total_cmp(&d(b))).unwrap();
+
+    let mut keep = vec![false; n];
+    keep[0] = true;
+    keep[split] = true;
+    keep[n - 1] = true;
+    dp_keep(&pts, 0, split, tolerance, &mut keep);
+    dp_keep(&pts, split, n - 1, tolerance, &mut keep);
+
+    // keep a third corner if everything collapsed onto the start to split line
+    if keep.iter().filter(|k| **k).count() < 4 {
+        let third = (1..n - 1)
+            .filter(|&k| k != split)
+            .max_by(|&a, &b| {
+                segment_distance(pts[a], pts[0], pts[split])
+                    .total_cmp(&segment_distance(pts[b], pts[0], pts[split]))
+            })
+            .unwrap();
+        keep[third] = true;
+    }
+
+    LineString::new(
+        pts.iter()
+            .zip(keep)
+            .filter_map(|(c, k)| k.then_some(*c))
+            .collect(),
+    )
+}
+
+/// Douglas-Peucker simplification of every ring from [`edges_to_multilinestring`]
+///
+/// Each ring is simplified with [`simplify_dp_ring`], so it stays closed with at least 4
+/// points.  If simplifying a ring makes it cross or touch itself it is tried again with
+/// half the tolerance, and so on down to a tolerance of 0.  At 0 only the vertices in the
+/// middle of straight runs go, which never changes the shape, so a simple ring always
+/// comes out simple.
+///
+/// # Parameters
+///
+/// - `mls`: The rings, in grid corners.
+/// - `epsilon`: The most a ring may move, in cells.  0 just drops colinear vertices.
+///
+/// # Notes
+///
+/// - Rings are simplified on their own, so a hole may end up crossing its exterior, or
+///   one region's ring another's.
+/// - The vertices kept are original corners, `f64` is only so the rings are ready for
+///   other `f64` geometry.
+///
+/// # Examples
+///
+/// ```
+/// use geo::LineString;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 0, 0],
+///     [1, 1, 0],
+///     [1, 1, 1],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let mls = geospatial::edges_to_multilinestring(1, &e[&1], &grid);
+/// assert_eq!(mls.0[0].0.len(), 13);
+///
+/// // the straight runs collapse
+/// let exact = geospatial::simplify_multilinestring(&mls, 0.0);
+/// assert_eq!(exact.0[0].0.len(), 9);
+///
+/// // and with a cell of slack the staircase is a triangle
+/// let rough = geospatial::simplify_multilinestring(&mls, 1.0);
+/// assert_eq!(rough.0[0], LineString::from(vec![
+///     (0.0, 0.0), (3.0, 3.0), (0.0, 3.0), (0.0, 0.0),
+/// ]));
+/// ```
+pub fn simplify_multilinestring(
+    mls: &MultiLineString<usize>,
+    epsilon: f64,
+) -> MultiLineString<f64> {
+    let simplify = |ring: &LineString<usize>| {
+        let ring: LineString<f64> = ring
+            .0
+            .iter()
+            .map(|c| Coord {
+                x: c.x as f64,
+                y: c.y as f64,
+            })
+            .collect();
+        let mut tolerance = epsilon.max(0.0);
+        loop {
+            let simple = simplify_dp_ring(&ring, tolerance);
+            let corners: Vec<Coord<usize>> = simple
+                .0
+                .iter()
+                .map(|c| Coord {
+                    x: c.x as usize,
+                    y: c.y as usize,
+                })
+                .collect();
+            if tolerance == 0.0 || ring_self_intersections(&corners).is_empty() {
+                return simple;
+            }
+            // halving forever would never quite get to 0
+            tolerance = if tolerance > 1e-3 {
+                tolerance / 2.0
+            } else {
+                0.0
+            };
+        }
+    };
+    MultiLineString::new(mls.iter().map(simplify).collect())
+}
+
+/// Rounds off the corners of rings with Chaikin's corner cutting
+///
+/// Each iteration swaps every segment `pq` for the two points a quarter and three quarters
+/// of the way along it, cutting a quarter off each side of every corner.  A few iterations
+/// turn the staircases of [`edges_to_multilinestring`] into smooth outlines, rounder than
+/// [`simplify_multilinestring`] gives.
+///
+/// # Parameters
+///
+/// - `mls`: The rings, or open lines.
+/// - `iterations`: How many times to cut, each doubles the number of vertices.  At 0 the
+///   rings come back unchanged.
+///
+/// # Returns
+///
+/// A closed ring stays closed, with its closing corner cut like any other, so it no longer
+/// starts at one of its original vertices.  An open line keeps both its ends.
+///
+/// # Notes
+///
+/// - Every new point is on a segment of the old ring, so a ring never leaves its bounding
+///   box, and it shrinks slightly into convex corners.
+/// - At a knot, where a ring touches itself at a corner, both corners are cut away from it
+///   and the ring comes apart into a simple one.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString, MultiLineString};
+///
+/// let square = LineString::from(vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0), (0.0, 0.0)]);
+/// let mls = MultiLineString::new(vec![square]);
+/// assert_eq!(geospatial::chaikin_smooth(&mls, 0), mls);
+/// let round = geospatial::chaikin_smooth(&mls, 1);
+/// assert_eq!(round.0[0].0.len(), 9);
+/// assert!(round.0[0].is_closed());
+/// assert_eq!(round.0[0].0[0], Coord { x: 1.0, y: 0.0 });
+/// assert_eq!(round.0[0].0[1], Coord { x: 3.0, y: 0.0 });
+/// assert_eq!(round.0[0].0[2], Coord { x: 4.0, y: 1.0 });
+/// ```
+pub fn chaikin_smooth(mls: &MultiLineString<f64>, iterations: u32) -> MultiLineString<f64> {
+    let cut = |p: Coord<f64>, q: Coord<f64>| (p * 0.75 + q * 0.25, p * 0.25 + q * 0.75);
+    let smooth = |ls: &LineString<f64>| {
+        let closed = ls.0.len() > 3 && ls.is_closed();
+        let mut pts = ls.0.clone();
+        for _ in 0..iterations {
+            if pts.len() < 3 {
+                break;
+            }
+            let mut next = Vec::with_capacity(2 * pts.len());
+            if !closed {
+                next.push(pts[0]);
+            }
+            for w in pts.windows(2) {
+                let (a, b) = cut(w[0], w[1]);
+                next.extend([a, b]);
+            }
+            if closed {
+                next.push(next[0]);
+            } else {
+                next.push(pts[pts.len() - 1]);
+            }
+            pts = next;
+        }
+        LineString::new(pts)
+    };
+    MultiLineString::new(mls.0.iter().map(smooth).collect())
+}
+
+/// Every pair of 4-adjacent cells with different values
+///
+/// Yields `(p, q, a, b)` where `p` and `q` are the positions of the two cells (as
+/// `Coord { x: col, y: row }`), `a` is the value at `p` and `b` the value at `q`.  Each pair
+/// appears once, with `q` the right or down neighbour of `p`, scanning row by row and
+/// looking right before down.  This is the scan underneath [`shared_boundary`] and
+/// [`adjacency`], for building other analyses on without walking the indices again.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 2],
+///     [1, 1],
+/// ];
+/// let d: Vec<_> = geospatial::neighbor_diffs(&grid).collect();
+/// assert_eq!(d, vec![
+///     (Coord { x: 0, y: 0 }, Coord { x: 1, y: 0 }, 1, 2),
+///     (Coord { x: 1, y: 0 }, Coord { x: 1, y: 1 }, 2, 1),
+/// ]);
+/// ```
+pub fn neighbor_diffs<T>(
+    grid: &Array2<T>,
+) -> impl Iterator<Item = (Coord<usize>, Coord<usize>, T, T)> + '_
+where
+    T: Eq + Copy,
+{
+    let (nrows, ncols) = grid.dim();
+    (0..nrows).flat_map(move |r| {
+        (0..ncols).flat_map(move |c| {
+            let p = Coord { x: c, y: r };
+            let me = grid[[r, c]];
+            let right = (c + 1 < ncols).then(|| (Coord { x: c + 1, y: r }, grid[[r, c + 1]]));
+            let down = (r + 1 < nrows).then(|| (Coord { x: c, y: r + 1 }, grid[[r + 1, c]]));
+            [right, down]
+                .into_iter()
+                .flatten()
+                .filter(move |(_, other)| *other != me)
+                .map(move |(q, other)| (p, q, me, other))
+        })
+    })
+}
+
+/// The edges separating two particular labels
+///
+/// Returns only the edges that have `a` on one side and `b` on the other, in the order of a
+/// row by row scan.  The grid border is never included since there is nothing on the other
+/// side of it.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 2],
+///     [1, 3],
+///     [2, 2],
+/// ];
+/// assert_eq!(
+///     geospatial::shared_boundary(&grid, 1, 2),
+///     vec![
+///         (Coord { x: 1, y: 0 }, Coord { x: 1, y: 1 }),
+///         (Coord { x: 0, y: 2 }, Coord { x: 1, y: 2 }),
+///     ]
+/// );
+/// assert_eq!(geospatial::shared_boundary(&grid, 1, 4), vec![]);
+/// ```
+pub fn shared_boundary<T>(grid: &Array2<T>, a: T, b: T) -> Vec<GridEdge>
+where
+    T: Eq + Copy,
+{
+    neighbor_diffs(grid)
+        .filter(|&(_, _, p, q)| (p == a && q == b) || (p == b && q == a))
+        .map(|(p, q, _, _)| {
+            if q.y == p.y {
+                (q, Coord { x: q.x, y: q.y + 1 })
+            } else {
+                (q, Coord { x: q.x + 1, y: q.y })
+            }
+        })
+        .collect()
+}
+
+/// Region adjacency graph of a label grid
+///
+/// Maps every label to the set of labels it shares at least one edge with (4-connectivity).
+/// Like [`marching_squares`] each cell is compared with its right and down neighbours, using
+/// [`neighbor_diffs`].  A label is never adjacent to itself, and a label with no
+/// neighbours maps to an empty set.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+/// use std::collections::{HashMap, HashSet};
+///
+/// let grid = array![
+///     [1, 1, 2],
+///     [3, 1, 2],
+/// ];
+/// assert_eq!(
+///     geospatial::adjacency(&grid),
+///     HashMap::from([
+///         (1, HashSet::from([2, 3])),
+///         (2, HashSet::from([1])),
+///         (3, HashSet::from([1])),
+///     ])
+/// );
+/// assert_eq!(
+///     geospatial::adjacency(&array![[5]]),
+///     HashMap::from([(5, HashSet::new())])
+/// );
+/// ```
+pub fn adjacency<T>(grid: &Array2<T>) -> HashMap<T, HashSet<T>>
+where
+    T: Eq + Hash + Copy,
+{
+    let mut ret: HashMap<T, HashSet<T>> = HashMap::new();
+    for v in grid.iter() {
+        ret.entry(*v).or_default();
+    }
+    for (_, _, a, b) in neighbor_diffs(grid) {
+        ret.entry(a).or_default().insert(b);
+        ret.entry(b).or_default().insert(a);
+    }
+    ret
+}
+
+// one dimensional squared distance transform of felzenszwalb and huttenlocher, the lower
+// envelope of the parabolas rooted at every finite sample of f
+fn edt_1d(f: &[f64]) -> Vec<f64> {
+    let n = f.len();
+    let mut v: Vec<usize> = Vec::with_capacity(n);
+    let mut z: Vec<f64> = Vec::with_capacity(n);
+    for q in 0..n {
+        if !f[q].is_finite() {
+            continue;
+        }
+        while let Some(&p) = v.last() {
+            let s = ((f[q] + (q * q) as f64) - (f[p] + (p * p) as f64)) / (2 * (q - p)) as f64;
+            if s <= z[z.len() - 1] {
+                v.pop();
+                z.pop();
+            } else {
+                v.push(q);
+                z.push(s);
+                break;
+            }
+        }
+        if v.is_empty() {
+            v.push(q);
+            z.push(f64::NEG_INFINITY);
+        }
+    }
+    if v.is_empty() {
+        return vec![f64::INFINITY; n];
+    }
+    let mut k = 0;
+    (0..n)
+        .map(|q| {
+            while k + 1 < v.len() && z[k + 1] < q as f64 {
+                k += 1;
+            }
+            let d = q as f64 - v[k] as f64;
+            d * d + f[v[k]]
+        })
+        .collect()
+}
+
+/// Distance from every cell to the nearest region boundary
+///
+/// A boundary cell is one with a 4-neighbour of a different label, and these have a
+/// distance of 0.  Every other cell gets the Euclidean distance, in cells, between its
+/// center and the center of the nearest boundary cell.
+///
+/// The distances are exact, computed with the separable transform of Felzenszwalb and
+/// Huttenlocher (a pass down every column then along every row), so the work is linear in
+/// the number of cells.
+///
+/// # Notes
+///
+/// - A grid with only one label has no boundary, and every distance is `f64::INFINITY`.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 1, 1, 1],
+///     [1, 1, 1, 1, 1],
+///     [1, 1, 1, 1, 2],
+/// ];
+/// let d = geospatial::distance_transform(&grid);
+/// assert_eq!(d[[2, 4]], 0.0);
+/// assert_eq!(d[[2, 3]], 0.0);
+/// assert_eq!(d[[1, 4]], 0.0);
+/// assert_eq!(d[[2, 0]], 3.0);
+/// assert_eq!(d[[0, 3]], 2f64.sqrt());
+/// assert_eq!(d[[0, 0]], 13f64.sqrt());
+/// ```
+pub fn distance_transform<T>(grid: &Array2<T>) -> Array2<f64>
+where
+    T: Eq + Copy,
+{
+    let (nrows, ncols) = grid.dim();
+    let mut d: Array2<f64> = Array2::from_shape_fn((nrows, ncols), |(r, c)| {
+        let me = grid[[r, c]];
+        let boundary = (r > 0 && grid[[r - 1, c]] != me)
+            || (r + 1 < nrows && grid[[r + 1, c]] != me)
+            || (c > 0 && grid[[r, c - 1]] != me)
+            || (c + 1 < ncols && grid[[r, c + 1]] != me);
+        if boundary { 0.0 } else { f64::INFINITY }
+    });
+
+    for mut col in d.columns_mut() {
+        let f = edt_1d(&col.to_vec());
+        col.assign(&ndarray::ArrayView1::from(&f));
+    }
+    for mut row in d.rows_mut() {
+        let f = edt_1d(&row.to_vec());
+        row.assign(&ndarray::ArrayView1::from(&f));
+    }
+    d.mapv_inplace(f64::sqrt);
+    d
+}
+
+// 1d dilation, out[i] is true if anything in f[i - radius..=i + radius] is
+fn dilate_1d(f: &[bool], radius: usize) -> Vec<bool> {
+    let mut prefix = vec![0usize; f.len() + 1];
+    for (i, &v) in f.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + v as usize;
+    }
+    (0..f.len())
+        .map(|i| {
+            let lo = i.saturating_sub(radius);
+            let hi = (i + radius + 1).min(f.len());
+            prefix[hi] > prefix[lo]
+        })
+        .collect()
+}
+
+// square (chebyshev) dilation of a mask, one 1d pass along the rows then the columns
+fn dilate_square(mask: &Array2<bool>, radius: usize) -> Array2<bool> {
+    let mut out = mask.clone();
+    for mut row in out.rows_mut() {
+        let f = dilate_1d(&row.to_vec(), radius);
+        row.assign(&ndarray::ArrayView1::from(&f));
+    }
+    for mut col in out.columns_mut() {
+        let f = dilate_1d(&col.to_vec(), radius);
+        col.assign(&ndarray::ArrayView1::from(&f));
+    }
+    out
+}
+
+/// Grows a region outward by `radius` cells
+///
+/// Returns a mask of every cell within `radius` steps of a cell labelled `label`.  With
+/// `Connectivity::Eight` diagonal steps count the same as straight ones (Chebyshev
+/// distance), so a single cell grows into a `(2 * radius + 1)` square.  With
+/// `Connectivity::Four` only straight steps are allowed (city block distance), and it grows
+/// into a diamond.
+///
+/// Either way the work is linear in the number of cells whatever the radius: the square is
+/// separable, a pass along every row and then every column, and the diamond comes from a
+/// two pass distance transform.
+///
+/// # Parameters
+///
+/// - `grid`: The labelled grid.
+/// - `label`: The region to grow.
+/// - `radius`: How far to grow it, `0` gives back just the region.
+/// - `connectivity`: Which steps are allowed, see above.
+///
+/// # Examples
+///
+/// ```
+/// use geospatial::Connectivity;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [0, 0, 0, 0, 0],
+///     [0, 0, 0, 0, 0],
+///     [0, 0, 0, 0, 7],
+/// ];
+/// assert_eq!(
+///     geospatial::dilate(&grid, 7, 1, Connectivity::Eight),
+///     array![
+///         [false, false, false, false, false],
+///         [false, false, false, true, true],
+///         [false, false, false, true, true],
+///     ]
+/// );
+/// assert_eq!(
+///     geospatial::dilate(&grid, 7, 1, Connectivity::Four),
+///     array![
+///         [false, false, false, false, false],
+///         [false, false, false, false, true],
+///         [false, false, false, true, true],
+///     ]
+/// );
+/// let count = |m: ndarray::Array2<bool>| m.iter().filter(|&&b| b).count();
+/// assert_eq!(count(geospatial::dilate(&grid, 7, 2, Connectivity::Eight)), 9);
+/// assert_eq!(count(geospatial::dilate(&grid, 7, 2, Connectivity::Four)), 6);
+/// assert_eq!(count(geospatial::dilate(&grid, 3, 2, Connectivity::Four)), 0);
+/// ```
+pub fn dilate<T>(
+    grid: &Array2<T>,
+    label: T,
+    radius: usize,
+    connectivity: Connectivity,
+) -> Array2<bool>
+where
+    T: PartialEq,
+{
+    dilate_mask(&grid.map(|v| *v == label), radius, connectivity)
+}
+
+// diamond (city block) dilation of a mask, from a two pass chamfer distance which is exact
+// for the city block metric
+fn dilate_diamond(mask: &Array2<bool>, radius: usize) -> Array2<bool> {
+    let (nrows, ncols) = mask.dim();
+    let far = radius.saturating_add(1);
+    let mut d = mask.map(|&b| if b { 0 } else { far });
+    for r in 0..nrows {
+        for c in 0..ncols {
+            if r > 0 {
+                d[[r, c]] = d[[r, c]].min(d[[r - 1, c]].saturating_add(1));
+            }
+            if c > 0 {
+                d[[r, c]] = d[[r, c]].min(d[[r, c - 1]].saturating_add(1));
+            }
+        }
+    }
+    for r in (0..nrows).rev() {
+        for c in (0..ncols).rev() {
+            if r + 1 < nrows {
+                d[[r, c]] = d[[r, c]].min(d[[r + 1, c]].saturating_add(1));
+            }
+            if c + 1 < ncols {
+                d[[r, c]] = d[[r, c]].min(d[[r, c + 1]].saturating_add(1));
+            }
+        }
+    }
+    d.map(|&v| v <= radius)
+}
+
+fn dilate_mask(mask: &Array2<bool>, radius: usize, connectivity: Connectivity) -> Array2<bool> {
+    match connectivity {
+        Connectivity::Four => dilate_diamond(mask, radius),
+        Connectivity::Eight => dilate_square(mask, radius),
+    }
+}
+
+/// Shrinks the `true` cells of a mask by `radius` cells
+///
+/// A cell stays `true` only if every cell within `radius` of it is `true`.  The
+/// structuring element comes from `connectivity`: `Four` measures distance in straight
+/// steps, so it is a diamond, and `Eight` allows diagonal steps, so it is a square.
+///
+/// # Notes
+///
+/// - Cells beyond the edge of the grid are ignored, so the grid edge doesn't eat into the
+///   mask.  This makes erosion the exact complement of dilating the `false` cells.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+/// use geospatial::Connectivity;
+///
+/// let mask = array![
+///     [true, true, true, false],
+///     [true, true, true, false],
+///     [true, true, true, true],
+/// ];
+/// assert_eq!(
+///     geospatial::erode(&mask, 1, Connectivity::Four),
+///     array![
+///         [true, true, false, false],
+///         [true, true, false, false],
+///         [true, true, true, false],
+///     ]
+/// );
+/// assert_eq!(
+///     geospatial::erode(&mask, 1, Connectivity::Eight),
+///     array![
+///         [true, true, false, false],
+///         [true, true, false, false],
+///         [true, true, false, false],
+///     ]
+/// );
+/// ```
+pub fn erode(mask: &Array2<bool>, radius: usize, connectivity: Connectivity) -> Array2<bool> {
+    dilate_mask(&mask.map(|b| !b), radius, connectivity).map(|b| !b)
+}
+
+/// Morphological opening, an erode followed by a dilate
+///
+/// Removes `true` protrusions and specks narrower than the structuring element (see
+/// [`erode`]) while leaving the bulk of each region where it was.  Run this on a binary
+/// mask before polygonizing to get rid of the single cell spurs that turn into ugly
+/// staircases.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+/// use geospatial::Connectivity;
+///
+/// let mask = array![
+///     [false, false, false, false, false, false],
+///     [false, true,  true,  true,  false, false],
+///     [false, true,  true,  true,  true,  true ],
+///     [false, true,  true,  true,  false, false],
+///     [false, false, false, false, false, true ],
+/// ];
+/// assert_eq!(
+///     geospatial::open(&mask, 1, Connectivity::Eight),
+///     array![
+///         [false, false, false, false, false, false],
+///         [false, true,  true,  true,  false, false],
+///         [false, true,  true,  true,  false, false],
+///         [false, true,  true,  true,  false, false],
+///         [false, false, false, false, false, false],
+///     ]
+/// );
+/// ```
+pub fn open(mask: &Array2<bool>, radius: usize, connectivity: Connectivity) -> Array2<bool> {
+    dilate_mask(&erode(mask, radius, connectivity), radius, connectivity)
+}
+
+/// Morphological closing, a dilate followed by an erode
+///
+/// The opposite of [`open`], it fills `false` notches and pinholes narrower than the
+/// structuring element.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+/// use geospatial::Connectivity;
+///
+/// let mask = array![
+///     [true, true,  true, false, false],
+///     [true, false, true, false, false],
+///     [true, true,  true, false, false],
+/// ];
+/// // the pinhole is filled, the wider strip of false survives
+/// assert_eq!(
+///     geospatial::close(&mask, 1, Connectivity::Eight),
+///     array![
+///         [true, true, true, false, false],
+///         [true, true, true, false, false],
+///         [true, true, true, false, false],
+///     ]
+/// );
+/// ```
+pub fn close(mask: &Array2<bool>, radius: usize, connectivity: Connectivity) -> Array2<bool> {
+    erode(
+        &dilate_mask(mask, radius, connectivity),
+        radius,
+        connectivity,
+    )
+}
+
+/// Centroid of each labelled region
+///
+/// The centroid is the mean position of the centers of a region's cells, in the same corner
+/// coordinates as [`marching_squares`], so the center of cell `[row, col]` is
+/// `(col + 0.5, row + 0.5)`.  This is the centroid `geo` would give the region's polygon,
+/// without having to build it.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 2],
+///     [1, 2, 2],
+/// ];
+/// let c = geospatial::region_centroids(&grid);
+/// assert_eq!(c[&1], Coord { x: 2.5 / 3.0, y: 2.5 / 3.0 });
+/// assert_eq!(c[&2], Coord { x: 6.5 / 3.0, y: 3.5 / 3.0 });
+/// ```
+pub fn region_centroids<T>(grid: &Array2<T>) -> HashMap<T, Coord<f64>>
+where
+    T: Eq + Hash + Copy,
+{
+    let mut sums: HashMap<T, (f64, f64, usize)> = HashMap::new();
+    for ((r, c), v) in grid.indexed_iter() {
+        let s = sums.entry(*v).or_insert((0.0, 0.0, 0));
+        s.0 += c as f64 + 0.5;
+        s.1 += r as f64 + 0.5;
+        s.2 += 1;
+    }
+    sums.into_iter()
+        .map(|(k, (x, y, n))| {
+            (
+                k,
+                Coord {
+                    x: x / n as f64,
+                    y: y / n as f64,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Bounding box of each labelled region
+///
+/// Returns the `Rect` around each region's cells, in the same corner coordinates as
+/// [`marching_squares`], so it lines up with the region's edges.  A region that is only
+/// cell `[row, col]` has min `(col, row)` and max `(col + 1, row + 1)`.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, Rect};
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 2],
+///     [1, 2, 2],
+/// ];
+/// let b = geospatial::region_bounds(&grid);
+/// assert_eq!(b[&1], Rect::new(Coord { x: 0, y: 0 }, Coord { x: 2, y: 2 }));
+/// assert_eq!(b[&2].min(), Coord { x: 1, y: 0 });
+/// assert_eq!(b[&2].max(), Coord { x: 3, y: 2 });
+///
+/// // the edges are all inside the box
+/// let e = geospatial::marching_squares(&grid);
+/// assert!(e[&2].iter().all(|(a, _)| a.x >= b[&2].min().x && a.x <= b[&2].max().x));
+/// ```
+pub fn region_bounds<T>(grid: &Array2<T>) -> HashMap<T, geo::Rect<usize>>
+where
+    T: Eq + Hash + Copy,
+{
+    let mut boxes: HashMap<T, (Coord<usize>, Coord<usize>)> = HashMap::new();
+    for ((r, c), v) in grid.indexed_iter() {
+        let b = boxes
+            .entry(*v)
+            .or_insert((Coord { x: c, y: r }, Coord { x: c + 1, y: r + 1 }));
+        b.0.x = b.0.x.min(c);
+        b.0.y = b.0.y.min(r);
+        b.1.x = b.1.x.max(c + 1);
+        b.1.y = b.1.y.max(r + 1);
+    }
+    boxes
+        .into_iter()
+        .map(|(k, (min, max))| (k, geo::Rect::new(min, max)))
+        .collect()
+}
+
+// mean earth radius in meters, as used by geo
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+// great circle distance in meters between two lon/lat points in degrees
+fn haversine(a: Coord<f64>, b: Coord<f64>) -> f64 {
+    let (lat1, lat2) = (a.y.to_radians(), b.y.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.x - a.x).to_radians();
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+/// Real world length in meters of a rasterized path
+///
+/// Each cell's center is mapped to longitude/latitude with `transform`, and the Haversine
+/// distances between consecutive cells are summed.  Intended for the output of
+/// [`rasterize_linestring`] on a grid whose transform is in degrees.
+///
+/// # Returns
+///
+/// The length in meters, `0.0` for an empty path or one of a single cell.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use geospatial::Affine;
+///
+/// // one degree cells, two cells south along a meridian
+/// let t = Affine::from_origin_cell(Coord { x: 0.0, y: 1.0 }, 1.0);
+/// let cells = [Coord { x: 0, y: 0 }, Coord { x: 0, y: 1 }, Coord { x: 0, y: 2 }];
+/// let len = geospatial::path_length_haversine(&cells, &t);
+/// assert!((len - 2.0 * 111_195.08).abs() < 1.0);
+///
+/// assert_eq!(geospatial::path_length_haversine(&cells[..1], &t), 0.0);
+/// assert_eq!(geospatial::path_length_haversine(&[], &t), 0.0);
+/// ```
+pub fn path_length_haversine(cells: &[Coord<isize>], transform: &Affine) -> f64 {
+    let lonlat = |c: &Coord<isize>| {
+        transform.apply(Coord {
+            x: c.x as f64 + 0.5,
+            y: c.y as f64 + 0.5,
+        })
+    };
+    cells
+        .windows(2)
+        .map(|w| haversine(lonlat(&w[0]), lonlat(&w[1])))
+        .sum()
+}
+
+/// Traces the boundary of every region directly on the grid
+///
+/// An alternative to [`marching_squares`] followed by [`edges_to_multilinestring`], giving a
+/// second, independent implementation to cross-check against.  Each 4-connected region is
+/// traced around the edges of its cells, keeping the region on the same side, to give an
+/// outer trace plus one inner trace per hole.  Since the traces are wound consistently
+/// there is no need for a point-in-polygon test to find the holes.
+///
+/// # Returns
+///
+/// A HashMap mapping each unique grid value to a `MultiPolygon<usize>` with one polygon per
+/// 4-connected region of that value, in the order the regions are met scanning row by row.
+///
+/// # Notes
+///
+/// The vertex ordering differs from the edge assembly path:
+///
+/// - Exteriors are counter-clockwise (positive shoelace area) and start at the top left
+///   corner of the region's first cell, holes are clockwise.
+/// - Where two cells of a region only touch at a corner the trace never crosses between
+///   them, so diagonal neighbours become separate polygons rather than one pinched ring.
+/// - A consequence is that a hole that touches the outside at a corner is not a hole, the
+///   exterior ring just visits that corner twice.
+/// - Like the edge assembly, every grid corner along the boundary is a vertex.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString, Polygon};
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 1],
+///     [1, 0, 1],
+///     [1, 1, 1],
+/// ];
+/// let p = geospatial::trace_regions(&grid);
+/// assert_eq!(p[&1].0.len(), 1);
+/// assert_eq!(p[&1].0[0].exterior().0.len(), 13);
+/// assert_eq!(p[&1].0[0].interiors(), &[LineString::from(vec![
+///     Coord { x: 2, y: 1 },
+///     Coord { x: 1, y: 1 },
+///     Coord { x: 1, y: 2 },
+///     Coord { x: 2, y: 2 },
+///     Coord { x: 2, y: 1 },
+/// ])]);
+/// assert_eq!(p[&0].0, vec![Polygon::new(
+///     LineString::from(vec![
+///         Coord { x: 1, y: 1 },
+///         Coord { x: 2, y: 1 },
+///         Coord { x: 2, y: 2 },
+///         Coord { x: 1, y: 2 },
+///         Coord { x: 1, y: 1 },
+///     ]),
+///     vec![],
+/// )]);
+///
+/// // diagonal neighbours are separate polygons
+/// let grid = array![
+///     [1, 0],
+///     [0, 1],
+/// ];
+/// let p = geospatial::trace_regions(&grid);
+/// assert_eq!(p[&1].0.len(), 2);
+/// assert_eq!(p[&0].0.len(), 2);
+/// ```
+pub fn trace_regions<T>(grid: &Array2<T>) -> HashMap<T, MultiPolygon<usize>>
+where
+    T: Eq + Hash + Copy,
+{
+    let (nrows, ncols) = grid.dim();
+    let mut visited: Array2<bool> = Array2::from_elem((nrows, ncols), false);
+    let mut ret: HashMap<T, MultiPolygon<usize>> = HashMap::new();
+
+    for r in 0..nrows {
+        for c in 0..ncols {
+            if visited[[r, c]] {
+                continue;
+            }
+            let cells = flood_fill(grid, Coord { x: c, y: r }, Connectivity::Four);
+            for cell in &cells {
+                visited[[cell.y, cell.x]] = true;
+            }
+            let inside: HashSet<Coord<usize>> = cells.iter().copied().collect();
+            let is_in = |r: usize, c: usize| inside.contains(&Coord { x: c, y: r });
+
+            // directed boundary edges, going counter-clockwise around each cell.  each
+            // remembers the cell it belongs to, which sorts out corners where two cells of
+            // the region only touch diagonally
+            let mut order: Vec<(Coord<usize>, usize)> = Vec::new();
+            let mut out: HashMap<Coord<usize>, Vec<(Coord<usize>, usize)>> = HashMap::new();
+            for (i, cell) in cells.iter().enumerate() {
+                let (r, c) = (cell.y, cell.x);
+                let (tl, tr) = (Coord { x: c, y: r }, Coord { x: c + 1, y: r });
+                let (bl, br) = (Coord { x: c, y: r + 1 }, Coord { x: c + 1, y: r + 1 });
+                let sides = [
+                    (r == 0 || !is_in(r - 1, c), tl, tr),
+                    (!is_in(r, c + 1), tr, br),
+                    (!is_in(r + 1, c), br, bl),
+                    (c == 0 || !is_in(r, c - 1), bl, tl),
+                ];
+                for (boundary, a, b) in sides {
+                    if boundary {
+                        order.push((a, i));
+                        out.entry(a).or_default().push((b, i));
+                    }
+                }
+            }
+
+            // cells[0] is the region's top left cell, so the first trace, starting on its
+            // top edge, is the exterior and anything else is a hole
+            let mut rings: Vec<LineString<usize>> = Vec::new();
+            for (start, cell) in order {
+                let Some(n) = out.get(&start) else { continue };
+                if !n.iter().any(|e| e.1 == cell) {
+                    continue;
+                }
+                let mut ring = vec![start];
+                let (mut cur, mut cell) = (start, cell);
+                loop {
+                    let n = out.get_mut(&cur).unwrap();
+                    let k = if n.len() == 1 {
+                        0
+                    } else {
+                        n.iter().position(|e| e.1 == cell).unwrap()
+                    };
+                    let (next, next_cell) = n.swap_remove(k);
+                    ring.push(next);
+                    (cur, cell) = (next, next_cell);
+                    if cur == start && !out[&cur].iter().any(|e| e.1 == cell) {
+                        break;
+                    }
+                }
+                rings.push(LineString::new(ring));
+            }
+            let mut rings = rings.into_iter();
+            let exterior = rings.next().unwrap();
+            ret.entry(grid[[r, c]])
+                .or_insert_with(|| MultiPolygon::new(vec![]))
+                .0
+                .push(geo::Polygon::new(exterior, rings.collect()));
+        }
+    }
+    ret
+}
+
+/// Polygons of every region, tidied up and guaranteed valid ring by ring
+///
+/// The polygons of [`trace_regions`], with optionally the colinear vertices along the
+/// staircase runs removed, and optionally each ring simplified with [`simplify_dp_ring`].
+/// Every ring that comes out is closed with at least 4 points and a non-zero area: a hole
+/// that collapses is dropped, and so is a whole polygon whose exterior does.
+///
+/// # Parameters
+///
+/// - `grid`: The labelled grid.
+/// - `collinear`: Remove vertices in the middle of straight runs.
+/// - `tolerance`: If given, the Douglas-Peucker tolerance, in cells.
+///
+/// # Notes
+///
+/// - Simplifying each ring on its own can make neighbouring regions, or a hole and its
+///   exterior, overlap slightly.  Removing colinear vertices never changes the shape.
+///
+/// # Examples
+///
+/// ```
+/// use geo::LineString;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 1],
+///     [1, 1, 1],
+///     [2, 2, 1],
+/// ];
+/// let p = geospatial::polygonize_simplified(&grid, true, None);
+/// assert_eq!(p[&2].0[0].exterior(), &LineString::from(vec![
+///     (0.0, 2.0), (2.0, 2.0), (2.0, 3.0), (0.0, 3.0), (0.0, 2.0),
+/// ]));
+/// assert_eq!(p[&1].0[0].exterior().0.len(), 7);
+/// ```
+pub fn polygonize_simplified<T>(
+    grid: &Array2<T>,
+    collinear: bool,
+    tolerance: Option<f64>,
+) -> HashMap<T, MultiPolygon<f64>>
+where
+    T: Eq + Hash + Copy,
+{
+    let tidy = |ring: &LineString<usize>, exterior: bool| -> Option<LineString<f64>> {
+        let mut pts = if collinear {
+            clean_ring(ring, exterior)
+        } else {
+            ring.0.clone()
+        };
+        if pts.first() != pts.last() {
+            pts.extend(pts.first().copied());
+        }
+        let mut ls: LineString<f64> = pts
+            .iter()
+            .map(|c| Coord {
+                x: c.x as f64,
+                y: c.y as f64,
+            })
+            .collect();
+        if let Some(tolerance) = tolerance {
+            ls = simplify_dp_ring(&ls, tolerance);
+        }
+        let area: f64 =
+            ls.0.windows(2)
+                .map(|w| w[0].x * w[1].y - w[1].x * w[0].y)
+                .sum();
+        (ls.is_closed() && ls.0.len() >= 4 && area != 0.0).then_some(ls)
+    };
+
+    trace_regions(grid)
+        .into_iter()
+        .filter_map(|(label, mp)| {
+            let polys: Vec<geo::Polygon<f64>> =
+                mp.0.iter()
+                    .filter_map(|p| {
+                        let exterior = tidy(p.exterior(), true)?;
+                        let holes = p
+                            .interiors()
+                            .iter()
+                            .filter_map(|h| tidy(h, false))
+                            .collect();
+                        Some(geo::Polygon::new(exterior, holes))
+                    })
+                    .collect();
+            (!polys.is_empty()).then(|| (label, MultiPolygon::new(polys)))
+        })
+        .collect()
+}
+
+/// Number of pieces and holes of every region
+///
+/// Maps each label to `(components, holes)`, counted from the rings of [`trace_regions`]:
+/// one component per outer ring, one hole per inner ring.  A solid blob is `(1, 0)` and a
+/// donut `(1, 1)`, and `components - holes` is the region's Euler number.
+///
+/// # Notes
+///
+/// - Components are 4-connected, so cells touching only at a corner are separate
+///   components.  The gaps are the other way round, a hole touching the outside, or another
+///   hole, at a corner is joined to it.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [1, 1, 1, 0, 2],
+///     [1, 0, 1, 0, 0],
+///     [1, 1, 1, 0, 2],
+/// ];
+/// let t = geospatial::region_topology(&grid);
+/// assert_eq!(t[&1], (1, 1));
+/// assert_eq!(t[&2], (2, 0));
+/// assert_eq!(t[&0], (2, 0));
+/// ```
+pub fn region_topology<T>(grid: &Array2<T>) -> HashMap<T, (usize, usize)>
+where
+    T: Eq + Hash + Copy,
+{
+    trace_regions(grid)
+        .into_iter()
+        .map(|(label, mp)| {
+            let holes = mp.0.iter().map(|p| p.interiors().len()).sum();
+            (label, (mp.0.len(), holes))
+        })
+        .collect()
+}
+
+// a ring with no closing point, repeated points or colinear points, wound counter-clockwise
+// (or clockwise if !ccw)
+fn clean_ring(ring: &LineString<usize>, ccw: bool) -> Vec<Coord<usize>> {
+    let mut pts: Vec<Coord<usize>> = ring.0.clone();
+    pts.dedup();
+    if pts.len() > 1 && pts.first() == pts.last() {
+        pts.pop();
+    }
+    loop {
+        let n = pts.len();
+        if n < 3 {
+            return vec![];
+        }
+        let keep: Vec<Coord<usize>> = (0..n)
+            .filter(|&i| orient(pts[(i + n - 1) % n], pts[i], pts[(i + 1) % n]) != 0)
+            .map(|i| pts[i])
+            .collect();
+        if keep.len() == n {
+            break;
+        }
+        pts = keep;
+    }
+    let mut closed = pts.clone();
+    closed.push(pts[0]);
+    if (twice_signed_area(&closed) > 0) != ccw {
+        pts.reverse();
+    }
+    pts
+}
+
+// whether m is inside the corner of a counter-clockwise polygon at v, between u and w
+fn in_wedge(u: Coord<usize>, v: Coord<usize>, w: Coord<usize>, m: Coord<usize>) -> bool {
+    if orient(u, v, w) >= 0 {
+        orient(u, v, m) >= 0 && orient(v, w, m) >= 0
+    } else {
+        orient(u, v, m) >= 0 || orient(v, w, m) >= 0
+    }
+}
+
+// whether the corner of a counter-clockwise polygon at v, between u and w, overlaps the
+// corner at v of the counter-clockwise triangle v, r, s by more than a shared side
+fn corners_overlap(
+    u: Coord<usize>,
+    v: Coord<usize>,
+    w: Coord<usize>,
+    r: Coord<usize>,
+    s: Coord<usize>,
+) -> bool {
+    let d = |p: Coord<usize>| (p.x as i128 - v.x as i128, p.y as i128 - v.y as i128);
+    let cross = |a: (i128, i128), b: (i128, i128)| a.0 * b.1 - a.1 * b.0;
+    let (du, dw, dr, ds) = (d(u), d(w), d(r), d(s));
+    let dm = (dr.0 + ds.0, dr.1 + ds.1);
+    let in_corner = |x| cross(dr, x) > 0 && cross(x, ds) > 0;
+    let in_polygon = if cross(dw, du) > 0 {
+        cross(dm, du) > 0 && cross(dw, dm) > 0
+    } else {
+        cross(dm, du) > 0 || cross(dw, dm) > 0
+    };
+    in_polygon || in_corner(du) || in_corner(dw)
+}
+
+// joins a clockwise hole into a counter-clockwise polygon by a pair of coincident edges,
+// from the hole's rightmost vertex to a polygon vertex it can see (eberly's method), or
+// at a vertex they share
+fn bridge_hole(outer: &mut Vec<Coord<usize>>, hole: &[Coord<usize>]) {
+    let n = outer.len();
+
+    // a hole that touches the polygon at a vertex is joined right there, passing it
+    // through the polygon's corner so neither copy of the vertex overlaps the other
+    for hi in 0..hole.len() {
+        let v = hole[hi];
+        let after = hole[(hi + 1) % hole.len()];
+        let copies: Vec<usize> = (0..n).filter(|&k| outer[k] == v).collect();
+        let Some(&first) = copies.first() else {
+            continue;
+        };
+        let pi = copies
+            .iter()
+            .copied()
+            .find(|&k| in_wedge(outer[(k + n - 1) % n], v, outer[(k + 1) % n], after))
+            .unwrap_or(first);
+        let mut merged = Vec::with_capacity(n + hole.len());
+        merged.extend_from_slice(&outer[..=pi]);
+        merged.extend_from_slice(&hole[hi + 1..]);
+        merged.extend_from_slice(&hole[..=hi]);
+        merged.extend_from_slice(&outer[pi + 1..]);
+        *outer = merged;
+        return;
+    }
+
+    let mi = (0..hole.len())
+        .max_by_key(|&i| (hole[i].x, hole[i].y))
+        .unwrap();
+    let m = hole[mi];
+
+    // nearest upward edge hit by a ray from m in +x
+    let mut hit: Option<(f64, usize)> = None;
+    for i in 0..n {
+        let (a, b) = (outer[i], outer[(i + 1) % n]);
+        if a.y < b.y && a.y <= m.y && m.y <= b.y {
+            let x =
+                a.x as f64 + (m.y - a.y) as f64 * (b.x as f64 - a.x as f64) / (b.y - a.y) as f64;
+            if x >= m.x as f64 && hit.is_none_or(|h| x < h.0) {
+                hit = Some((x, i));
+            }
+        }
+    }
+    let Some((ix, i)) = hit else { return };
+    let (a, b) = (outer[i], outer[(i + 1) % n]);
+    let mut p = if a.y == m.y && a.x as f64 == ix {
+        a
+    } else if b.y == m.y && b.x as f64 == ix {
+        b
+    } else if a.x > b.x {
+        a
+    } else {
+        b
+    };
+
+    // something may be in the way, then the vertex in the triangle m, hit, p closest in
+    // angle to the ray is visible
+    if p.y != m.y {
+        let tri = [
+            (m.x as f64, m.y as f64),
+            (ix, m.y as f64),
+            (p.x as f64, p.y as f64),
+        ];
+        let side = |o: (f64, f64), d: (f64, f64), q: (f64, f64)| {
+            (d.0 - o.0) * (q.1 - o.1) - (d.1 - o.1) * (q.0 - o.0)
+        };
+        let inside = |q: (f64, f64)| {
+            let s = [
+                side(tri[0], tri[1], q),
+                side(tri[1], tri[2], q),
+                side(tri[2], tri[0], q),
+            ];
+            s.iter().all(|v| *v >= 0.0) || s.iter().all(|v| *v <= 0.0)
+        };
+        let mut best = p;
+        for &r in outer.iter() {
+            if r == p || r.x <= m.x || !inside((r.x as f64, r.y as f64)) {
+                continue;
+            }
+            // compare |dy| / dx between r and best, then how far
+            let (rdy, rdx) = (r.y.abs_diff(m.y) as i128, (r.x - m.x) as i128);
+            let (bdy, bdx) = (best.y.abs_diff(m.y) as i128, (best.x - m.x) as i128);
+            if (rdy * bdx, rdx) < (bdy * rdx, bdx) {
+                best = r;
+            }
+        }
+        p = best;
+    }
+
+    // p may be a pinch vertex that appears more than once, pick the copy facing m
+    let copies: Vec<usize> = (0..n).filter(|&k| outer[k] == p).collect();
+    let pi = copies
+        .iter()
+        .copied()
+        .find(|&k| in_wedge(outer[(k + n - 1) % n], p, outer[(k + 1) % n], m))
+        .unwrap_or(copies[0]);
+
+    let mut merged = Vec::with_capacity(n + hole.len() + 2);
+    merged.extend_from_slice(&outer[..=pi]);
+    merged.extend_from_slice(&hole[mi..]);
+    merged.extend_from_slice(&hole[..=mi]);
+    merged.extend_from_slice(&outer[pi..]);
+    *outer = merged;
+}
+
+/// Triangulates a polygon, holes and all, for filling in a renderer
+///
+/// The holes are first joined to the exterior with bridging edges, then the resulting
+/// outline is cut into triangles by ear clipping.  All the tests are done exactly in integer
+/// arithmetic, bar choosing where to bridge a hole, so the long colinear runs of raster
+/// staircases don't upset it, they are simply dropped before clipping.
+///
+/// # Returns
+///
+/// Triangles whose areas add up to the polygon's, each counter-clockwise in the shoelace
+/// sense, as `f64` ready for a vertex buffer.
+///
+/// # Notes
+///
+/// - The number of triangles is `n + 2h - 2` for `n` non-colinear vertices and `h` holes.
+/// - Holes may touch the exterior, or each other, at a vertex, as they do in the polygons
+///   from [`region_to_multipolygon`].  They are joined at that vertex and don't count
+///   towards `h`.
+/// - Heavily deformed input, such as self-intersecting rings, gets some triangulation but
+///   not necessarily one that covers the polygon.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{LineString, Polygon};
+///
+/// let poly = Polygon::new(
+///     LineString::from(vec![(0, 0), (1, 0), (2, 0), (3, 0), (3, 3), (0, 3), (0, 0)]),
+///     vec![LineString::from(vec![(1, 1), (1, 2), (2, 2), (2, 1), (1, 1)])],
+/// );
+/// let tris = geospatial::triangulate(&poly);
+/// assert_eq!(tris.len(), 8);
+/// let area: f64 = tris
+///     .iter()
+///     .map(|[a, b, c]| ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)) / 2.0)
+///     .sum();
+/// assert_eq!(area, 8.0);
+///
+/// // the hole at cell (1, 1) touches the exterior at the corner it shares with cell (0, 0)
+/// let grid = ndarray::array![[1, 0, 0], [0, 1, 0], [0, 0, 0]];
+/// let e = geospatial::marching_squares(&grid);
+/// let mp = geospatial::region_to_multipolygon(0, &e[&0], &grid);
+/// assert_eq!(mp.0[0].interiors().len(), 1);
+/// let tris = geospatial::triangulate(&mp.0[0]);
+/// assert_eq!(tris.len(), 8);
+/// let area: f64 = tris
+///     .iter()
+///     .map(|[a, b, c]| ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)) / 2.0)
+///     .sum();
+/// assert_eq!(area, 7.0);
+/// ```
+pub fn triangulate(poly: &geo::Polygon<usize>) -> Vec<[Coord<f64>; 3]> {
+    let mut pts = clean_ring(poly.exterior(), true);
+    if pts.is_empty() {
+        return vec![];
+    }
+    let mut holes: Vec<Vec<Coord<usize>>> = poly
+        .interiors()
+        .iter()
+        .map(|h| clean_ring(h, false))
+        .filter(|h| !h.is_empty())
+        .collect();
+    holes.sort_by_key(|h| std::cmp::Reverse(h.iter().map(|p| p.x).max()));
+    for hole in &holes {
+        bridge_hole(&mut pts, hole);
+    }
+
+    let n = pts.len();
+    let mut prev: Vec<usize> = (0..n).map(|i| (i + n - 1) % n).collect();
+    let mut next: Vec<usize> = (0..n).map(|i| (i + 1) % n).collect();
+    let to_f64 = |p: Coord<usize>| Coord {
+        x: p.x as f64,
+        y: p.y as f64,
+    };
+
+    // only reflex vertices can be inside an ear, and vertices only ever become less
+    // reflex as ears are cut off, so bucket the ones that start reflex by position to
+    // find those near an ear quickly.  A vertex that appears more than once, where a hole
+    // touches the exterior or a bridge, can be in the way of an ear at one of its other
+    // copies even when convex, so those are bucketed too
+    let (x0, y0) = (
+        pts.iter().map(|p| p.x).min().unwrap(),
+        pts.iter().map(|p| p.y).min().unwrap(),
+    );
+    let (x1, y1) = (
+        pts.iter().map(|p| p.x).max().unwrap(),
+        pts.iter().map(|p| p.y).max().unwrap(),
+    );
+    let size = (((x1 - x0 + 1) as f64 * (y1 - y0 + 1) as f64 / n as f64).sqrt() as usize).max(1);
+    let (bw, bh) = ((x1 - x0) / size + 1, (y1 - y0) / size + 1);
+    let bucket = |p: Coord<usize>| ((p.x - x0) / size, (p.y - y0) / size);
+    let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); bw * bh];
+    let mut copies: HashMap<Coord<usize>, usize> = HashMap::new();
+    for p in &pts {
+        *copies.entry(*p).or_default() += 1;
+    }
+    for k in 0..n {
+        if copies[&pts[k]] > 1 || orient(pts[prev[k]], pts[k], pts[next[k]]) <= 0 {
+            let (bx, by) = bucket(pts[k]);
+            buckets[by * bw + bx].push(k);
+        }
+    }
+    let mut removed = vec![false; n];
+
+    // nothing else may be inside the ear, or if strict, strictly inside.  Another copy of
+    // one of the ear's own corners is only in the way if the polygon's corner there
+    // reaches into the ear
+    let is_ear = |prev: &[usize], next: &[usize], removed: &[bool], b: usize, strict: bool| {
+        let (a, c) = (prev[b], next[b]);
+        let (pa, pb, pc) = (pts[a], pts[b], pts[c]);
+        let lo = bucket(Coord {
+            x: pa.x.min(pb.x).min(pc.x),
+            y: pa.y.min(pb.y).min(pc.y),
+        });
+        let hi = bucket(Coord {
+            x: pa.x.max(pb.x).max(pc.x),
+            y: pa.y.max(pb.y).max(pc.y),
+        });
+        for by in lo.1..=hi.1 {
+            for bx in lo.0..=hi.0 {
+                for &k in &buckets[by * bw + bx] {
+                    let p = pts[k];
+                    if removed[k] || k == a || k == b || k == c {
+                        continue;
+                    }
+                    let (u, w) = (pts[prev[k]], pts[next[k]]);
+                    let corner = if p == pa {
+                        Some((pb, pc))
+                    } else if p == pb {
+                        Some((pc, pa))
+                    } else if p == pc {
+                        Some((pa, pb))
+                    } else {
+                        None
+                    };
+                    if let Some((r, s)) = corner {
+                        if corners_overlap(u, p, w, r, s) {
+                            return false;
+                        }
+                        continue;
+                    }
+                    if orient(u, p, w) > 0 {
+                        continue;
+                    }
+                    let s = [orient(pa, pb, p), orient(pb, pc, p), orient(pc, pa, p)];
+                    let inside = if strict {
+                        s.iter().all(|v| *v > 0)
+                    } else {
+                        s.iter().all(|v| *v >= 0)
+                    };
+                    if inside {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    };
+
+    let mut tris = Vec::with_capacity(n.saturating_sub(2));
+    let (mut remaining, mut i, mut stalled, mut strict) = (n, 0, 0, false);
+    while remaining > 2 {
+        let (a, c) = (prev[i], next[i]);
+        let o = orient(pts[a], pts[i], pts[c]);
+        // colinear, including the spikes where a bridge doubles back, adds no area
+        let clip = o == 0
+            || (o > 0 && (stalled > 2 * remaining || is_ear(&prev, &next, &removed, i, strict)));
+        if clip {
+            if o > 0 {
+                tris.push([to_f64(pts[a]), to_f64(pts[i]), to_f64(pts[c])]);
+            }
+            next[a] = c;
+            prev[c] = a;
+            removed[i] = true;
+            remaining -= 1;
+            (i, stalled, strict) = (c, 0, false);
+            continue;
+        }
+        i = c;
+        stalled += 1;
+        if stalled > remaining {
+            // no proper ear, allow ones that only touch something, and failing that take
+            // any convex corner
+            strict = true;
+        }
+        if stalled > 3 * remaining {
+            break;
+        }
+    }
+    tris
+}
+
+/// A shared boundary problem found by [`snap_shared_boundaries`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryMismatch<T> {
+    /// both regions have this unit edge with themselves on the same side, so they overlap
+    Overlap { edge: GridEdge, labels: (T, T) },
+    /// the region's boundary runs along this unit edge, in the same direction, more than once
+    Repeated { edge: GridEdge, label: T },
+}
+
+// the lattice points strictly between a and b
+fn lattice_between(a: Coord<usize>, b: Coord<usize>) -> Vec<Coord<usize>> {
+    let (dx, dy) = (b.x as isize - a.x as isize, b.y as isize - a.y as isize);
+    let (mut g, mut h) = (dx.unsigned_abs(), dy.unsigned_abs());
+    while h != 0 {
+        (g, h) = (h, g % h);
+    }
+    (1..g)
+        .map(|i| Coord {
+            x: (a.x as isize + dx / g as isize * i as isize) as usize,
+            y: (a.y as isize + dy / g as isize * i as isize) as usize,
+        })
+        .collect()
+}
+
+/// Makes the shared boundaries of neighbouring regions use the same vertices, and checks
+/// they are consistent
+///
+/// Polygons from [`trace_regions`] (or the edge assembly) are all integer, so where two
+/// regions meet they already agree exactly.  But once colinear vertices have been dropped
+/// one side of a boundary can have a vertex, where a third region meets it, that the other
+/// side skips over: a T-junction.  Every such vertex is inserted into the rings that pass
+/// straight through it, so each shared boundary is the same sequence of vertices from both
+/// sides and can't drift apart under later transforms.
+///
+/// At the same time every boundary is broken into unit steps between lattice points and
+/// checked.  Exteriors are taken to keep their region on the left (counter-clockwise) and
+/// holes on the right, whichever way they are actually wound, so two regions claiming the
+/// same side of a step overlap.
+///
+/// # Returns
+///
+/// Every problem found, in order of label and then ring.  An empty `Vec` means the regions
+/// tile without overlaps.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString, MultiPolygon, Polygon};
+/// use geospatial::BoundaryMismatch;
+/// use std::collections::HashMap;
+///
+/// let rect = |x0: usize, y0: usize, x1: usize, y1: usize| MultiPolygon::new(vec![Polygon::new(
+///     LineString::from(vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1), (x0, y0)]),
+///     vec![],
+/// )]);
+/// // 1 fills the left column, 2 and 3 are stacked on the right
+/// let mut polys = HashMap::from([
+///     (1, rect(0, 0, 1, 2)),
+///     (2, rect(1, 0, 2, 1)),
+///     (3, rect(1, 1, 2, 2)),
+/// ]);
+/// assert_eq!(geospatial::snap_shared_boundaries(&mut polys), vec![]);
+/// // 1 now has the corner where 2 and 3 meet
+/// assert_eq!(polys[&1].0[0].exterior(), &LineString::from(vec![
+///     (0, 0), (1, 0), (1, 1), (1, 2), (0, 2), (0, 0),
 /// ]));
+///
+/// // 4 covers the same cell as 2
+/// polys.insert(4, rect(1, 0, 2, 1));
+/// let report = geospatial::snap_shared_boundaries(&mut polys);
+/// assert_eq!(report.len(), 4);
+/// assert_eq!(report[0], BoundaryMismatch::Overlap {
+///     edge: (Coord { x: 1, y: 0 }, Coord { x: 2, y: 0 }),
+///     labels: (2, 4),
+/// });
+/// ```
+pub fn snap_shared_boundaries<T>(
+    polys: &mut HashMap<T, MultiPolygon<usize>>,
+) -> Vec<BoundaryMismatch<T>>
+where
+    T: Eq + Hash + Copy + Ord,
+{
+    let mut labels: Vec<T> = polys.keys().copied().collect();
+    labels.sort();
+    let vertices: HashSet<Coord<usize>> = polys
+        .values()
+        .flat_map(|mp| mp.0.iter())
+        .flat_map(|p| std::iter::once(p.exterior()).chain(p.interiors()))
+        .flat_map(|ls| ls.0.iter().copied())
+        .collect();
+
+    let mut claimed: HashMap<GridEdge, T> = HashMap::new();
+    let mut report = Vec::new();
+    for label in labels {
+        let mp = polys.get_mut(&label).unwrap();
+        for poly in mp.0.iter_mut() {
+            let mut rings = Vec::new();
+            for (k, ring) in std::iter::once(poly.exterior())
+                .chain(poly.interiors())
+                .enumerate()
+            {
+                // steps go with the region on the left
+                let flip = (twice_signed_area(&ring.0) > 0) != (k == 0);
+                let mut snapped: Vec<Coord<usize>> = Vec::with_capacity(ring.0.len());
+                for w in ring.0.windows(2) {
+                    snapped.push(w[0]);
+                    let mut prev = w[0];
+                    for p in lattice_between(w[0], w[1]).into_iter().chain([w[1]]) {
+                        if p != w[1] && vertices.contains(&p) {
+                            snapped.push(p);
+                        }
+                        let edge = if flip { (p, prev) } else { (prev, p) };
+                        match claimed.get(&edge) {
+                            Some(&other) if other == label => {
+                                report.push(BoundaryMismatch::Repeated { edge, label })
+                            }
+                            Some(&other) => report.push(BoundaryMismatch::Overlap {
+                                edge,
+                                labels: (other, label),
+                            }),
+                            None => {
+                                claimed.insert(edge, label);
+                            }
+                        }
+                        prev = p;
+                    }
+                }
+                snapped.extend(ring.0.last());
+                rings.push(LineString::new(snapped));
+            }
+            let exterior = rings.remove(0);
+            *poly = geo::Polygon::new(exterior, rings);
+        }
+    }
+    report
+}
+
+/// Stitch the polygons of two neighbouring tiles together along the seam between them
+///
+/// For polygonizing a raster too big to hold at once: polygonize it in tiles, say with
+/// [`trace_regions`], move each tile's polygons to where the tile sits in the whole raster,
+/// and merge them in one tile at a time.  A region that carries on across the seam comes
+/// out of its two tiles as two polygons sharing edges along column `seam_x`.  Those edges
+/// cancel out and the rest of the boundary is joined up again into one polygon, which may
+/// have gained holes where the parts met more than once.
+///
+/// # Parameters
+///
+/// - `left`: the polygons to the left of the seam, keyed by label, merged into in place
+/// - `right`: the polygons to the right of the seam
+/// - `seam_x`: the lattice column the tiles meet along, so the last column of cells in
+///   the left tile is `seam_x - 1` and the first in the right one `seam_x`
+///
+/// # Notes
+///
+/// - Both sides must be in the coordinates of the whole raster.
+/// - Exteriors should be wound counter-clockwise, by signed area, and holes clockwise, as
+///   [`trace_regions`] makes them.
+/// - Polygons that don't touch the seam, and labels only on one side, are just carried
+///   over.  The merged polygons have a vertex at every lattice point along their
+///   boundaries and are wound the same way.
+/// - Where two parts of a region only touch at a corner the boundary is cut there, as in
+///   [`edges_to_multilinestring`], so the parts stay separate polygons.
+///
+/// # Examples
+///
+/// ```
+/// use geo::MapCoords;
+/// use ndarray::{array, s};
+///
 /// let grid = array![
-///     [0, 1],
-///     [1, 1],
+///     [1, 1, 1, 1],
+///     [1, 0, 0, 1],
+///     [1, 1, 1, 1],
 /// ];
-/// let e = geospatial::marching_squares(&grid);
-/// let mls = geospatial::edges_to_multilinestring(0, &e[&0], &grid);
+/// let mut left = geospatial::trace_regions(&grid.slice(s![.., ..2]).to_owned());
+/// let right: std::collections::HashMap<_, _> =
+///     geospatial::trace_regions(&grid.slice(s![.., 2..]).to_owned())
+///         .into_iter()
+///         .map(|(k, v)| (k, v.map_coords(|c| geo::Coord { x: c.x + 2, y: c.y })))
+///         .collect();
+/// geospatial::merge_tile_boundaries(&mut left, &right, 2);
+///
+/// // the ring of 1s is one polygon with a hole again, the same as the whole grid gives
+/// let whole = geospatial::trace_regions(&grid);
+/// assert_eq!(left[&1].0.len(), 1);
+/// assert_eq!(left[&1].0[0].interiors().len(), 1);
+/// assert_eq!(left[&1].0[0].exterior().0.len(), whole[&1].0[0].exterior().0.len());
+/// assert_eq!(left[&0].0.len(), 1);
+/// assert_eq!(geospatial::polygon_area(left[&0].0[0].exterior()), 2);
+/// ```
+pub fn merge_tile_boundaries<T>(
+    left: &mut HashMap<T, MultiPolygon<usize>>,
+    right: &HashMap<T, MultiPolygon<usize>>,
+    seam_x: usize,
+) where
+    T: Eq + Hash + Copy,
+{
+    let on_seam = |p: &geo::Polygon<usize>| {
+        p.exterior()
+            .0
+            .windows(2)
+            .any(|w| w[0].x == seam_x && w[1].x == seam_x && w[0].y != w[1].y)
+    };
+
+    for (label, theirs) in right {
+        let ours = left
+            .entry(*label)
+            .or_insert_with(|| MultiPolygon::new(vec![]));
+        let (mut joining, kept): (Vec<_>, Vec<_>) = ours.0.drain(..).partition(on_seam);
+        ours.0 = kept;
+        let (theirs_joining, theirs_kept): (Vec<_>, Vec<_>) =
+            theirs.0.iter().partition(|p| on_seam(p));
+        ours.0.extend(theirs_kept.into_iter().cloned());
+        if joining.is_empty() || theirs_joining.is_empty() {
+            ours.0.extend(joining);
+            ours.0.extend(theirs_joining.into_iter().cloned());
+            continue;
+        }
+        joining.extend(theirs_joining.into_iter().cloned());
+
+        // every ring as unit steps with the region on the left, cancelling each step
+        // against one going back the other way
+        let mut steps: Vec<GridEdge> = Vec::new();
+        let mut alive: Vec<bool> = Vec::new();
+        let mut index: HashMap<GridEdge, Vec<usize>> = HashMap::new();
+        for poly in &joining {
+            let rings = std::iter::once((poly.exterior(), true))
+                .chain(poly.interiors().iter().map(|h| (h, false)));
+            for (ring, ccw) in rings {
+                let mut pts = ring.0.clone();
+                if (twice_signed_area(&pts) > 0) != ccw {
+                    pts.reverse();
+                }
+                for w in pts.windows(2) {
+                    let path: Vec<Coord<usize>> = std::iter::once(w[0])
+                        .chain(lattice_between(w[0], w[1]))
+                        .chain(std::iter::once(w[1]))
+                        .collect();
+                    for s in path.windows(2) {
+                        let (a, b) = (s[0], s[1]);
+                        match index.get_mut(&(b, a)).and_then(|v| v.pop()) {
+                            Some(j) => alive[j] = false,
+                            None => {
+                                index.entry((a, b)).or_default().push(steps.len());
+                                steps.push((a, b));
+                                alive.push(true);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // join what's left back up, turning as far left as possible so each ring hugs its
+        // own cells, and cutting a ring wherever it comes back to a vertex
+        let mut from: HashMap<Coord<usize>, Vec<usize>> = HashMap::new();
+        for (i, (a, _)) in steps.iter().enumerate() {
+            if alive[i] {
+                from.entry(*a).or_default().push(i);
+            }
+        }
+        let turn = |i: usize, j: usize| {
+            let ((a, b), (_, c)) = (steps[i], steps[j]);
+            let d = (b.x as isize - a.x as isize, b.y as isize - a.y as isize);
+            let e = (c.x as isize - b.x as isize, c.y as isize - b.y as isize);
+            (d.0 * e.1 - d.1 * e.0, d.0 * e.0 + d.1 * e.1)
+        };
+        let mut rings: Vec<Vec<Coord<usize>>> = Vec::new();
+        for start in 0..steps.len() {
+            if !alive[start] {
+                continue;
+            }
+            let mut path = vec![steps[start].0];
+            let mut at: HashMap<Coord<usize>, usize> = HashMap::from([(steps[start].0, 0)]);
+            let mut cur = start;
+            loop {
+                alive[cur] = false;
+                let b = steps[cur].1;
+                if let Some(&k) = at.get(&b) {
+                    for q in &path[k + 1..] {
+                        at.remove(q);
+                    }
+                    let mut ring = path.split_off(k);
+                    ring.push(b);
+                    rings.push(ring);
+                } else {
+                    at.insert(b, path.len());
+                }
+                path.push(b);
+                let outs = from.get_mut(&b).unwrap();
+                outs.retain(|&j| alive[j]);
+                match outs.iter().copied().max_by_key(|&j| turn(cur, j)) {
+                    Some(j) => cur = j,
+                    None => break,
+                }
+            }
+        }
+
+        // holes go in the smallest exterior around the cell on their left
+        let (exteriors, holes): (Vec<_>, Vec<_>) =
+            rings.into_iter().partition(|r| twice_signed_area(r) > 0);
+        let mut interiors: Vec<Vec<LineString<usize>>> = vec![Vec::new(); exteriors.len()];
+        for hole in holes {
+            let (a, b) = (hole[0], hole[1]);
+            let p = Coord {
+                x: (a.x + b.x) as f64 / 2.0 - (b.y as f64 - a.y as f64) / 2.0,
+                y: (a.y + b.y) as f64 / 2.0 + (b.x as f64 - a.x as f64) / 2.0,
+            };
+            let parent = (0..exteriors.len())
+                .filter(|&i| ring_contains(&exteriors[i], p))
+                .min_by_key(|&i| twice_signed_area(&exteriors[i]))
+                .expect("a hole is always inside an exterior");
+            interiors[parent].push(LineString::new(hole));
+        }
+        ours.0.extend(
+            exteriors
+                .into_iter()
+                .zip(interiors)
+                .map(|(e, h)| geo::Polygon::new(LineString::new(e), h)),
+        );
+    }
+}
+
+/// Iso-contours of a continuous field at a given level
+///
+/// [`contour_scaled`] with unit cells for any numeric grid, so sample `grid[[row, col]]` is
+/// at the center of its cell, `(col + 0.5, row + 0.5)`, and the contours cross the lines
+/// between samples at the linearly interpolated point.  Saddles, the grid edge and `NaN`
+/// are all handled as described there.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [0u8, 0, 0],
+///     [0, 4, 0],
+///     [0, 0, 0],
+/// ];
+/// let mls = geospatial::contour(&grid, 1.0);
+/// assert_eq!(mls.0, vec![LineString::from(vec![
+///     Coord { x: 0.75, y: 1.5 },
+///     Coord { x: 1.5, y: 0.75 },
+///     Coord { x: 2.25, y: 1.5 },
+///     Coord { x: 1.5, y: 2.25 },
+///     Coord { x: 0.75, y: 1.5 },
+/// ])]);
+///
+/// // a high edge is closed off along the outermost samples
+/// let grid = array![[1, 1], [0, 0]];
+/// let mls = geospatial::contour(&grid, 0.5);
 /// assert_eq!(mls.0.len(), 1);
-/// assert_eq!(mls.0[0], LineString::from(vec![
-///    Coord { x: 0, y: 0 },
-///    Coord { x: 0, y: 1 },
-///    Coord { x: 1, y: 1 },
-///    Coord { x: 1, y: 0 },
-///    Coord { x: 0, y: 0 },
-/// ]));
+/// assert!(mls.0[0].is_closed());
+/// ```
+pub fn contour<T>(grid: &Array2<T>, level: f64) -> MultiLineString<f64>
+where
+    T: Into<f64> + Copy,
+{
+    contour_scaled(&grid.mapv(Into::into), level, 1.0, 1.0)
+}
+
+/// Iso-contours of a continuous field at a given level, with anisotropic cell sizes
+///
+/// This is the classic marching squares with linear interpolation, for elevation or
+/// probability rasters rather than region labels.  Each sample `grid[[row, col]]` sits at
+/// the center of its cell, `((col + 0.5) * dx, (row + 0.5) * dy)`, and contour vertices are
+/// placed where the linearly interpolated field crosses `level` along the lines between
+/// samples.  The interpolation fraction is found between the samples and then the position
+/// is scaled, so cells that are `dx` wide and `dy` high come out right.
+///
+/// # Returns
+///
+/// A `MultiLineString<f64>` of closed rings, plus open lines where contours run into
+/// missing data.  With `y` increasing down the rows the values at or above `level` are on
+/// the left of every line, in the shoelace sense, so a ring around a peak has positive
+/// signed area.  The open lines come first, then the rings.
+///
+/// # Notes
+///
+/// - A value equal to `level` counts as above it.
+/// - The ambiguous saddle squares, where diagonally opposite corners are above, are
+///   resolved by the mean of the four corners: if it is above `level` the two high corners
+///   are joined, otherwise they are separated.
+/// - Contours that run off the edge of the grid are closed along the outermost samples.  So
+///   a strip of high samples only one sample wide along the edge gives a ring with no area.
+/// - `NaN` samples are missing data.  A square with a `NaN` corner has no crossings, so a
+///   contour stops at the edge of the valid data rather than jumping across the gap, and
+///   comes back as an open `LineString`.  Unlike the grid edge, gaps are not closed off.
+/// - Consecutive duplicate vertices are removed, and rings that collapse to less than a
+///   triangle dropped.
+///
+/// # Examples
+///
+/// ```
+/// use geo::{Coord, LineString};
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [0.0, 0.0, 0.0],
+///     [0.0, 1.0, 0.0],
+///     [0.0, 0.0, 0.0],
+/// ];
+/// let mls = geospatial::contour_scaled(&grid, 0.5, 2.0, 3.0);
+/// assert_eq!(mls.0, vec![LineString::from(vec![
+///     Coord { x: 2.0, y: 4.5 },
+///     Coord { x: 3.0, y: 3.0 },
+///     Coord { x: 4.0, y: 4.5 },
+///     Coord { x: 3.0, y: 6.0 },
+///     Coord { x: 2.0, y: 4.5 },
+/// ])]);
+///
+/// // the bottom row is missing, so the contour is cut open there
+/// let grid = array![
+///     [0.0, 0.0, 0.0],
+///     [0.0, 1.0, 0.0],
+///     [f64::NAN, f64::NAN, f64::NAN],
+/// ];
+/// let mls = geospatial::contour_scaled(&grid, 0.5, 1.0, 1.0);
+/// assert_eq!(mls.0, vec![LineString::from(vec![
+///     Coord { x: 1.0, y: 1.5 },
+///     Coord { x: 1.5, y: 1.0 },
+///     Coord { x: 2.0, y: 1.5 },
+/// ])]);
+/// assert!(!mls.0[0].is_closed());
+/// ```
+pub fn contour_scaled(grid: &Array2<f64>, level: f64, dx: f64, dy: f64) -> MultiLineString<f64> {
+    // a crossing lives on the line between two samples, identified by the first sample (in
+    // the padded grid) and whether the line is horizontal
+    type Key = (usize, usize, bool);
+
+    // pad with samples below any level, so every contour is closed inside the padding
+    let (nrows, ncols) = grid.dim();
+    if nrows == 0 || ncols == 0 {
+        return MultiLineString::new(vec![]);
+    }
+    let mut padded: Array2<f64> = Array2::from_elem((nrows + 2, ncols + 2), f64::NEG_INFINITY);
+    padded
+        .slice_mut(ndarray::s![1..=nrows, 1..=ncols])
+        .assign(grid);
+
+    let pos = |r: usize, c: usize| Coord {
+        x: (c as f64 - 0.5) * dx,
+        y: (r as f64 - 0.5) * dy,
+    };
+    // where the field crosses level between samples a and b
+    let crossing = |a: (usize, usize), b: (usize, usize)| {
+        let (va, vb) = (padded[a], padded[b]);
+        let t = if vb.is_infinite() {
+            0.0
+        } else if va.is_infinite() {
+            1.0
+        } else {
+            (level - va) / (vb - va)
+        };
+        let (pa, pb) = (pos(a.0, a.1), pos(b.0, b.1));
+        Coord {
+            x: pa.x + t * (pb.x - pa.x),
+            y: pa.y + t * (pb.y - pa.y),
+        }
+    };
+
+    // every segment runs from the crossing where the square's boundary, walked around
+    // positively, leaves the high corners to where it next (or previously) enters them
+    let mut order: Vec<Key> = Vec::new();
+    let mut next: HashMap<Key, Key> = HashMap::new();
+    let mut points: HashMap<Key, Coord<f64>> = HashMap::new();
+    for r in 0..nrows + 1 {
+        for c in 0..ncols + 1 {
+            let corners = [(r, c), (r, c + 1), (r + 1, c + 1), (r + 1, c)];
+            let sides: [Key; 4] = [
+                (r, c, true),
+                (r, c + 1, false),
+                (r + 1, c, true),
+                (r, c, false),
+            ];
+            if corners.iter().any(|p| padded[*p].is_nan()) {
+                continue;
+            }
+            let high = corners.map(|p| padded[p] >= level);
+
+            // (key, leaving the high corners) for every side that is crossed
+            let mut crossed: Vec<(Key, bool)> = Vec::with_capacity(4);
+            for i in 0..4 {
+                let (a, b) = (corners[i], corners[(i + 1) % 4]);
+                if high[i] != high[(i + 1) % 4] {
+                    points.entry(sides[i]).or_insert_with(|| crossing(a, b));
+                    crossed.push((sides[i], high[i]));
+                }
+            }
+            if crossed.is_empty() {
+                continue;
+            }
+            let forward = if crossed.len() == 4 {
+                let mean = corners.iter().map(|p| padded[*p]).sum::<f64>() / 4.0;
+                mean >= level
+            } else {
+                true
+            };
+            let n = crossed.len();
+            for i in 0..n {
+                let (key, leaving) = crossed[i];
+                if leaving {
+                    let to = if forward {
+                        (i + 1) % n
+                    } else {
+                        (i + n - 1) % n
+                    };
+                    order.push(key);
+                    next.insert(key, crossed[to].0);
+                }
+            }
+        }
+    }
+
+    // chain the segments, first the open lines from each crossing nothing leads to (these
+    // only happen next to NaN), then what's left are rings
+    let targets: HashSet<Key> = next.values().copied().collect();
+    let heads: Vec<Key> = order
+        .iter()
+        .copied()
+        .filter(|k| !targets.contains(k))
+        .collect();
+    let mut lines: Vec<LineString<f64>> = Vec::new();
+    for (open, start) in heads
+        .into_iter()
+        .map(|k| (true, k))
+        .chain(order.into_iter().map(|k| (false, k)))
+    {
+        if !next.contains_key(&start) {
+            continue;
+        }
+        let mut line: Vec<Coord<f64>> = vec![points[&start]];
+        let mut cur = start;
+        while let Some(n) = next.remove(&cur) {
+            let p = points[&n];
+            if line.last() != Some(&p) {
+                line.push(p);
+            }
+            cur = n;
+        }
+        if line.len() >= if open { 2 } else { 4 } {
+            lines.push(LineString::new(line));
+        }
+    }
+    MultiLineString::new(lines)
+}
+
+// where the field crosses level between two samples, always worked out from the same end
+// so neighbouring pieces that share the line get exactly the same point
+fn band_crossing(a: (Coord<f64>, f64), b: (Coord<f64>, f64), level: f64) -> Coord<f64> {
+    let (a, b) = if (a.0.x, a.0.y) <= (b.0.x, b.0.y) {
+        (a, b)
+    } else {
+        (b, a)
+    };
+    let t = (level - a.1) / (b.1 - a.1);
+    Coord {
+        x: a.0.x + t * (b.0.x - a.0.x),
+        y: a.0.y + t * (b.0.y - a.0.y),
+    }
+}
+
+// sutherland-hodgman clip of a piece of a linear field to values >= level, or < level
+fn band_clip(piece: &[(Coord<f64>, f64)], level: f64, above: bool) -> Vec<(Coord<f64>, f64)> {
+    let inside = |v: f64| if above { v >= level } else { v < level };
+    let mut out = Vec::with_capacity(piece.len() + 2);
+    for i in 0..piece.len() {
+        let (a, b) = (piece[i], piece[(i + 1) % piece.len()]);
+        if inside(a.1) {
+            out.push(a);
+        }
+        if inside(a.1) != inside(b.1) {
+            out.push((band_crossing(a, b, level), level));
+        }
+    }
+    out
+}
+
+/// Filled contours, the regions of a field between consecutive levels
+///
+/// Where [`contour_scaled`] gives the lines at one level, this gives the areas between
+/// levels as polygons, for shading.  With levels `l0 < l1 < ... < ln` the bands are
+/// `(-inf, l0)`, `[l0, l1)`, ... `[ln, inf)`.
+///
+/// Samples are at the centers of their cells, `(col + 0.5, row + 0.5)`, as in
+/// [`contour_scaled`] with `dx` and `dy` of 1.  Each square of four samples is split into
+/// four triangles meeting at its center, which gets the mean of the corners, and the field
+/// is taken to be linear over each triangle.  The band polygons are then exact for that
+/// field, and neighbouring bands share their boundaries exactly.
+///
+/// # Parameters
+///
+/// - `grid`: the samples
+/// - `levels`: the band edges, in increasing order
+///
+/// # Returns
+///
+/// One `(low, high, polygons)` for each band, lowest first, where `low` is
+/// `f64::NEG_INFINITY` for the first band and `high` is `f64::INFINITY` for the last.
+/// Bands with no area have empty `polygons`.  Exterior rings have positive signed area,
+/// in the shoelace sense, and holes negative.
+///
+/// # Notes
+///
+/// - A value equal to a level is in the band above it.
+/// - Saddle squares are resolved by the mean of the corners, as in [`contour_scaled`].
+/// - The bands only cover the area between the outermost samples, half a cell in from the
+///   edge of the grid.
+/// - Squares with a `NaN` corner are missing data and in no band.
+///
+/// # Panics
+///
+/// If `levels` is not in increasing order.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Area;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [0.0, 0.0, 0.0],
+///     [0.0, 2.0, 0.0],
+///     [0.0, 0.0, 0.0],
+/// ];
+/// let bands = geospatial::contour_bands(&grid, &[1.0]);
+/// assert_eq!(bands.len(), 2);
+/// let (low, high, below) = &bands[0];
+/// assert_eq!((*low, *high), (f64::NEG_INFINITY, 1.0));
+/// let (_, _, above) = &bands[1];
+///
+/// // the peak is an octagon, and what's below it a square with an octagonal hole
+/// assert_eq!(above.0.len(), 1);
+/// assert_eq!(above.0[0].exterior().0.len(), 9);
+/// assert!((above.unsigned_area() - 2.0 / 3.0).abs() < 1e-12);
+/// assert_eq!(below.0[0].interiors().len(), 1);
+/// assert!((below.unsigned_area() + above.unsigned_area() - 4.0).abs() < 1e-12);
+/// ```
+pub fn contour_bands(grid: &Array2<f64>, levels: &[f64]) -> Vec<(f64, f64, MultiPolygon<f64>)> {
+    assert!(
+        levels.windows(2).all(|w| w[0] < w[1]),
+        "levels must be increasing"
+    );
+    type Key = (u64, u64);
+    let key = |p: Coord<f64>| (p.x.to_bits(), p.y.to_bits());
+
+    // the four triangles of every square, positively wound
+    let (nrows, ncols) = grid.dim();
+    let mut triangles: Vec<[(Coord<f64>, f64); 3]> = Vec::new();
+    for r in 0..nrows.saturating_sub(1) {
+        for c in 0..ncols.saturating_sub(1) {
+            let corners = [(r, c), (r, c + 1), (r + 1, c + 1), (r + 1, c)].map(|(r, c)| {
+                let p = Coord {
+                    x: c as f64 + 0.5,
+                    y: r as f64 + 0.5,
+                };
+                (p, grid[[r, c]])
+            });
+            if corners.iter().any(|(_, v)| v.is_nan()) {
+                continue;
+            }
+            let mean = corners.iter().map(|(_, v)| v).sum::<f64>() / 4.0;
+            let center = (
+                Coord {
+                    x: c as f64 + 1.0,
+                    y: r as f64 + 1.0,
+                },
+                mean,
+            );
+            for i in 0..4 {
+                triangles.push([corners[i], corners[(i + 1) % 4], center]);
+            }
+        }
+    }
+
+    let bounds: Vec<f64> = std::iter::once(f64::NEG_INFINITY)
+        .chain(levels.iter().copied())
+        .chain(std::iter::once(f64::INFINITY))
+        .collect();
+    let mut out = Vec::with_capacity(bounds.len() - 1);
+    for w in bounds.windows(2) {
+        let (low, high) = (w[0], w[1]);
+
+        // the band's piece of every triangle, with the edges pieces share cancelled out
+        let mut edges: Vec<(Coord<f64>, Coord<f64>)> = Vec::new();
+        let mut alive: Vec<bool> = Vec::new();
+        let mut index: HashMap<(Key, Key), Vec<usize>> = HashMap::new();
+        for tri in &triangles {
+            let (lo, hi) = tri
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), t| {
+                    (lo.min(t.1), hi.max(t.1))
+                });
+            if hi < low || lo >= high {
+                continue;
+            }
+            let mut piece = tri.to_vec();
+            if low > f64::NEG_INFINITY {
+                piece = band_clip(&piece, low, true);
+            }
+            if high < f64::INFINITY {
+                piece = band_clip(&piece, high, false);
+            }
+            let mut pts: Vec<Coord<f64>> = piece.into_iter().map(|(p, _)| p).collect();
+            pts.dedup();
+            while pts.len() > 1 && pts.first() == pts.last() {
+                pts.pop();
+            }
+            if pts.len() < 3 {
+                continue;
+            }
+            for i in 0..pts.len() {
+                let (a, b) = (pts[i], pts[(i + 1) % pts.len()]);
+                match index.get_mut(&(key(b), key(a))).and_then(|v| v.pop()) {
+                    Some(j) => alive[j] = false,
+                    None => {
+                        index.entry((key(a), key(b))).or_default().push(edges.len());
+                        edges.push((a, b));
+                        alive.push(true);
+                    }
+                }
+            }
+        }
+
+        // chain what's left into rings, at a pinch taking the sharpest left turn so each
+        // ring stays round its own bit of the band, and cutting a ring off whenever the
+        // walk comes back to a vertex it has already been through
+        let mut from: HashMap<Key, Vec<usize>> = HashMap::new();
+        for (i, e) in edges.iter().enumerate().rev() {
+            if alive[i] {
+                from.entry(key(e.0)).or_default().push(i);
+            }
+        }
+        let mut loops: Vec<Vec<Coord<f64>>> = Vec::new();
+        for start in 0..edges.len() {
+            if !alive[start] {
+                continue;
+            }
+            let mut path = vec![edges[start].0];
+            let mut at: HashMap<Key, usize> = HashMap::from([(key(edges[start].0), 0)]);
+            let mut cur = start;
+            loop {
+                alive[cur] = false;
+                let (a, b) = edges[cur];
+                if let Some(&k) = at.get(&key(b)) {
+                    for p in &path[k + 1..] {
+                        at.remove(&key(*p));
+                    }
+                    let mut ring = path.split_off(k);
+                    ring.push(b);
+                    loops.push(ring);
+                    path.push(b);
+                } else {
+                    at.insert(key(b), path.len());
+                    path.push(b);
+                }
+                let Some(outs) = from.get_mut(&key(b)) else {
+                    break;
+                };
+                outs.retain(|&j| alive[j]);
+                let turn = |j: usize| {
+                    let d = (b.x - a.x, b.y - a.y);
+                    let e = (edges[j].1.x - b.x, edges[j].1.y - b.y);
+                    (d.0 * e.1 - d.1 * e.0).atan2(d.0 * e.0 + d.1 * e.1)
+                };
+                match outs
+                    .iter()
+                    .copied()
+                    .max_by(|&i, &j| turn(i).total_cmp(&turn(j)))
+                {
+                    Some(j) => cur = j,
+                    None => break,
+                }
+            }
+        }
+
+        // drop the vertices in the middle of straight runs
+        let mut rings: Vec<LineString<f64>> = Vec::new();
+        for mut ring in loops {
+            ring.pop();
+            let n = ring.len();
+            let mut pts: Vec<Coord<f64>> = Vec::with_capacity(n + 1);
+            for i in 0..n {
+                let (a, b, c) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+                if (b.x - a.x) * (c.y - b.y) != (b.y - a.y) * (c.x - b.x) {
+                    pts.push(b);
+                }
+            }
+            if pts.len() >= 3 {
+                pts.push(pts[0]);
+                rings.push(LineString::new(pts));
+            }
+        }
+
+        // holes are wound negatively, and go in the smallest exterior just to their left
+        let area = |ls: &LineString<f64>| {
+            ls.0.windows(2)
+                .map(|w| w[0].x * w[1].y - w[1].x * w[0].y)
+                .sum::<f64>()
+                / 2.0
+        };
+        let contains = |ls: &LineString<f64>, p: Coord<f64>| {
+            let mut inside = false;
+            for w in ls.0.windows(2) {
+                let (a, b) = (w[0], w[1]);
+                if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) * (b.x - a.x) / (b.y - a.y)
+                {
+                    inside = !inside;
+                }
+            }
+            inside
+        };
+        let (exteriors, holes): (Vec<LineString<f64>>, Vec<LineString<f64>>) =
+            rings.into_iter().partition(|ls| area(ls) > 0.0);
+        let mut interiors: Vec<Vec<LineString<f64>>> = vec![Vec::new(); exteriors.len()];
+        for hole in holes {
+            let (a, b) = (hole.0[0], hole.0[1]);
+            let eps = 1e-6;
+            let p = Coord {
+                x: (a.x + b.x) / 2.0 - eps * (b.y - a.y),
+                y: (a.y + b.y) / 2.0 + eps * (b.x - a.x),
+            };
+            let parent = (0..exteriors.len())
+                .filter(|&i| contains(&exteriors[i], p))
+                .min_by(|&i, &j| area(&exteriors[i]).total_cmp(&area(&exteriors[j])));
+            if let Some(i) = parent {
+                interiors[i].push(hole);
+            }
+        }
+        let polygons = exteriors
+            .into_iter()
+            .zip(interiors)
+            .map(|(e, h)| geo::Polygon::new(e, h))
+            .collect();
+        out.push((low, high, MultiPolygon::new(polygons)));
+    }
+    out
+}
+
+// the cell on the left, in the shoelace sense, of the unit grid edge from a to b, if
+// there is one inside the grid.  returned as [row, col]
+fn cell_left_of<S: RawData>(
+    a: Coord<usize>,
+    b: Coord<usize>,
+    grid: &ArrayBase<S, Ix2>,
+) -> Option<[usize; 2]> {
+    let (nrows, ncols) = grid.dim();
+    let cell = if b.x > a.x {
+        Some([a.y, a.x])
+    } else if b.x < a.x {
+        a.y.checked_sub(1).map(|r| [r, b.x])
+    } else if b.y > a.y {
+        a.x.checked_sub(1).map(|c| [a.y, c])
+    } else {
+        a.y.checked_sub(1).map(|r| [r, a.x])
+    };
+    cell.filter(|[r, c]| *r < nrows && *c < ncols)
+}
+
+// the cell on the right, in the shoelace sense, of the unit grid edge from a to b
+fn cell_right_of<S: RawData>(
+    a: Coord<usize>,
+    b: Coord<usize>,
+    grid: &ArrayBase<S, Ix2>,
+) -> Option<[usize; 2]> {
+    cell_left_of(b, a, grid)
+}
+
+// whether p is inside a closed ring, by counting crossings of a ray heading in +x
+fn ring_contains(ring: &[Coord<usize>], p: Coord<f64>) -> bool {
+    let mut inside = false;
+    for w in ring.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let (ay, by) = (a.y as f64, b.y as f64);
+        if (ay > p.y) != (by > p.y) {
+            let x = a.x as f64 + (p.y - ay) / (by - ay) * (b.x as f64 - a.x as f64);
+            if x > p.x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// What part a ring plays in the boundary of a region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingKind {
+    /// an outer boundary
+    Outer,
+    /// a hole, inside the outer ring at this index
+    Inner(usize),
+}
+
+// the first step of a ring along a cell side, rings from eight connectivity can have
+// diagonals too
+fn first_unit_step(ring: &LineString<usize>) -> GridEdge {
+    ring.0
+        .windows(2)
+        .map(|w| (w[0], w[1]))
+        .find(|(a, b)| a.x == b.x || a.y == b.y)
+        .unwrap_or((ring.0[0], ring.0[1]))
+}
+
+// outer rings have the region on the same side as their own interior
+fn ring_is_outer<T: PartialEq + Copy>(ring: &LineString<usize>, id: T, grid: &Array2<T>) -> bool {
+    let (a, b) = first_unit_step(ring);
+    let ccw = twice_signed_area(&ring.0) > 0;
+    cell_left_of(a, b, grid).is_some_and(|ix| grid[ix] == id) == ccw
+}
+
+/// [`edges_to_multilinestring`], along with whether each ring is an outer boundary or a hole
+///
+/// When a region has several rings there is no telling from the `MultiLineString` alone
+/// whether they are separate parts of the region or holes in it.  Here each ring is also
+/// tagged with a [`RingKind`], and each hole with the index of the outer ring it is in.
+///
+/// A ring is a hole when the region is on the outside of it, which is found from the grid
+/// cells alongside the ring and the ring's winding.  A hole's outer ring is the smallest
+/// outer ring that contains the region cells alongside the hole, so islands inside holes
+/// come out right too.
+///
+/// # Examples
+///
+/// ```
+/// use geospatial::RingKind;
+/// use ndarray::array;
+///
 /// let grid = array![
-///     [1, 1],
-///     [1, 1],
+///     [1, 1, 1, 1, 1],
+///     [1, 0, 0, 0, 1],
+///     [1, 0, 1, 0, 1],
+///     [1, 0, 0, 0, 1],
+///     [1, 1, 1, 1, 1],
 /// ];
 /// let e = geospatial::marching_squares(&grid);
-/// let mls = geospatial::edges_to_multilinestring(1, &e[&1], &grid);
-/// assert_eq!(mls.0.len(), 1);
-/// assert_eq!(mls.0[0], LineString::from(vec![
-///    Coord { x: 0, y: 0 },
-///    Coord { x: 0, y: 1 },
-///    Coord { x: 0, y: 2 },
-///    Coord { x: 1, y: 2 },
-///    Coord { x: 2, y: 2 },
-///    Coord { x: 2, y: 1 },
-///    Coord { x: 2, y: 0 },
-///    Coord { x: 1, y: 0 },
-///    Coord { x: 0, y: 0 },
-/// ]));
+/// let (mls, kinds) = geospatial::assemble_with_topology(1, &e[&1], &grid);
+/// assert_eq!(mls.0.len(), 3);
+/// let outer = kinds.iter().position(|k| *k == RingKind::Outer).unwrap();
+/// assert_eq!(mls.0[outer].0.len(), 21);
+/// let hole = kinds.iter().position(|k| matches!(k, RingKind::Inner(_))).unwrap();
+/// assert_eq!(kinds[hole], RingKind::Inner(outer));
+/// assert_eq!(mls.0[hole].0.len(), 13);
+/// assert_eq!(kinds.iter().filter(|k| **k == RingKind::Outer).count(), 2);
+///
+/// let (mls, kinds) = geospatial::assemble_with_topology(0, &e[&0], &grid);
+/// assert_eq!(kinds, vec![RingKind::Outer, RingKind::Inner(0)]);
+/// assert_eq!(mls.0[1].0.len(), 5);
+/// ```
+pub fn assemble_with_topology<T>(
+    id: T,
+    edges: &[GridEdge],
+    grid: &Array2<T>,
+) -> (MultiLineString<usize>, Vec<RingKind>)
+where
+    T: Eq + Hash + Copy,
+{
+    let mls = edges_to_multilinestring(id, edges, grid);
+    let outer: Vec<bool> = mls
+        .0
+        .iter()
+        .map(|ring| ring_is_outer(ring, id, grid))
+        .collect();
+
+    let kinds = mls
+        .0
+        .iter()
+        .zip(&outer)
+        .map(|(ring, &is_outer)| {
+            if is_outer {
+                return RingKind::Outer;
+            }
+            let (a, b) = first_unit_step(ring);
+            let [r, c] = cell_left_of(a, b, grid)
+                .filter(|ix| grid[*ix] == id)
+                .or(cell_right_of(a, b, grid))
+                .unwrap();
+            // a quarter of the way in from the side, in the half a diagonal may leave
+            let p = Coord {
+                x: (a.x + b.x) as f64 / 4.0 + (c as f64 + 0.5) / 2.0,
+                y: (a.y + b.y) as f64 / 4.0 + (r as f64 + 0.5) / 2.0,
+            };
+            let parent = (0..mls.0.len())
+                .filter(|&j| outer[j] && ring_contains(&mls.0[j].0, p))
+                .min_by_key(|&j| twice_signed_area(&mls.0[j].0).abs())
+                .expect("a hole is always inside an outer ring");
+            RingKind::Inner(parent)
+        })
+        .collect();
+
+    (mls, kinds)
+}
+
+/// [`edges_to_multilinestring`], with each ring tagged as a hole or not
+///
+/// Whether a ring is a hole comes straight from the grid cells alongside its first edge
+/// and the way the ring winds, so no point-in-polygon tests are needed.  A ring with the
+/// region on its inside is an outer boundary, one with the region on its outside is a
+/// hole.  Use [`assemble_with_topology`] to also find which outer ring each hole is in.
+///
+/// # Parameters
+///
+/// * `id`, `edges`, `grid` - as for [`edges_to_multilinestring`]
+///
+/// # Returns
+///
+/// The `MultiLineString`, and each of its rings in the same order paired with `true` if
+/// it is a hole.
+///
+/// # Examples
+///
+/// ```
+/// use ndarray::array;
+///
 /// let grid = array![
-///     [0, 1, 0],
+///     [1, 1, 1],
 ///     [1, 0, 1],
-///     [0, 1, 0],
+///     [1, 1, 1],
 /// ];
 /// let e = geospatial::marching_squares(&grid);
-/// let mls = geospatial::edges_to_multilinestring(1, &e[&1], &grid);
-/// assert_eq!(mls.0.len(), 4);
-/// assert_eq!(mls.0[0], LineString::from(vec![
-///    Coord { x: 1, y: 0 },
-///    Coord { x: 1, y: 1 },
-///    Coord { x: 2, y: 1 },
-///    Coord { x: 2, y: 0 },
-///    Coord { x: 1, y: 0 },
-/// ]));
-/// assert_eq!(mls.0[1], LineString::from(vec![
-///    Coord { x: 1, y: 3 },
-///    Coord { x: 1, y: 2 },
-///    Coord { x: 2, y: 2 },
-///    Coord { x: 2, y: 3 },
-///    Coord { x: 1, y: 3 },
-/// ]));
-/// assert_eq!(mls.0[2], LineString::from(vec![
-///    Coord { x: 0, y: 1 },
-///    Coord { x: 1, y: 1 },
-///    Coord { x: 1, y: 2 },
-///    Coord { x: 0, y: 2 },
-///    Coord { x: 0, y: 1 },
-/// ]));
-/// assert_eq!(mls.0[3], LineString::from(vec![
-///    Coord { x: 3, y: 1 },
-///    Coord { x: 2, y: 1 },
-///    Coord { x: 2, y: 2 },
-///    Coord { x: 3, y: 2 },
-///    Coord { x: 3, y: 1 },
-/// ]));
+/// let (mls, rings) = geospatial::edges_to_multilinestring_with_holes(1, &e[&1], &grid);
+/// assert_eq!(rings.len(), mls.0.len());
+/// assert_eq!(rings.iter().filter(|(_, hole)| *hole).count(), 1);
+/// let (hole, _) = rings.iter().find(|(_, hole)| *hole).unwrap();
+/// assert_eq!(hole.0.len(), 5);
+///
+/// // the 0 in the middle has only an outer ring
+/// let (_, rings) = geospatial::edges_to_multilinestring_with_holes(0, &e[&0], &grid);
+/// assert!(rings.iter().all(|(_, hole)| !hole));
 /// ```
-pub fn edges_to_multilinestring<T>(
+pub fn edges_to_multilinestring_with_holes<T>(
     id: T,
-    edges: &Vec<(Coord<usize>, Coord<usize>)>,
+    edges: &[GridEdge],
     grid: &Array2<T>,
-) -> MultiLineString<usize>
+) -> (MultiLineString<usize>, Vec<(LineString<usize>, bool)>)
 where
     T: Eq + Hash + Copy,
 {
-    // return which two points are adjancent to our grid cell when we hit a knot
-    // p is previous
-    // c is where we are at
-    fn adjcoords<T>(p: Coord<usize>, c: Coord<usize>, id: T, grid: &Array2<T>) -> [Coord<usize>; 2]
-    where
-        T: Eq + Hash + Copy,
-    {
-        let (row, col) = (c.y, c.x);
+    let mls = edges_to_multilinestring(id, edges, grid);
+    let rings = mls
+        .0
+        .iter()
+        .map(|ring| (ring.clone(), !ring_is_outer(ring, id, grid)))
+        .collect();
+    (mls, rings)
+}
 
-        // this is kinda tricky, so be explicit
+/// Assemble one region's edges into polygons, with each hole in its outer boundary
+///
+/// [`assemble_with_topology`] works out which rings are holes and which outer ring each
+/// is in, from the side of each ring the region is on and, for holes, the smallest outer
+/// ring around them.  This wraps the result up as a `MultiPolygon`, one polygon for each
+/// outer ring.
+///
+/// # Parameters
+///
+/// - `id`, `edges`, `grid` - as for [`edges_to_multilinestring`]
+///
+/// # Returns
+///
+/// The region's polygons, in the order their outer rings were assembled.  Exteriors are
+/// wound counter-clockwise and holes clockwise, by signed area.
+///
+/// # Examples
+///
+/// ```
+/// use geospatial::polygon_area;
+/// use ndarray::array;
+///
+/// // two donuts of 1s, and a 1 in the middle of the right one
+/// let grid = array![
+///     [1, 1, 1, 0, 1, 1, 1, 1, 1],
+///     [1, 0, 1, 0, 1, 0, 0, 0, 1],
+///     [1, 1, 1, 0, 1, 0, 1, 0, 1],
+///     [0, 0, 0, 0, 1, 0, 0, 0, 1],
+///     [0, 0, 0, 0, 1, 1, 1, 1, 1],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let mp = geospatial::region_to_multipolygon(1, &e[&1], &grid);
+/// assert_eq!(mp.0.len(), 3);
+/// let mut areas: Vec<_> = mp
+///     .0
+///     .iter()
+///     .map(|p| {
+///         let holes: Vec<i64> = p.interiors().iter().map(polygon_area).collect();
+///         (polygon_area(p.exterior()), holes)
+///     })
+///     .collect();
+/// areas.sort();
+/// assert_eq!(areas, vec![(1, vec![]), (9, vec![-1]), (25, vec![-9])]);
+/// ```
+pub fn region_to_multipolygon<T>(id: T, edges: &[GridEdge], grid: &Array2<T>) -> MultiPolygon<usize>
+where
+    T: Eq + Hash + Copy,
+{
+    let (mls, kinds) = assemble_with_topology(id, edges, grid);
+    let mut holes: Vec<Vec<LineString<usize>>> = vec![Vec::new(); mls.0.len()];
+    for (ring, kind) in mls.0.iter().zip(&kinds) {
+        if let RingKind::Inner(i) = kind {
+            holes[*i].push(LineString::new(wound_ring(ring, false)));
+        }
+    }
+    let polygons = mls
+        .0
+        .iter()
+        .zip(&kinds)
+        .zip(holes)
+        .filter(|((_, kind), _)| **kind == RingKind::Outer)
+        .map(|((ring, _), holes)| geo::Polygon::new(LineString::new(wound_ring(ring, true)), holes))
+        .collect();
+    MultiPolygon::new(polygons)
+}
 
-        // moving right
-        if p.x == c.x - 1 {
-            // moving up
-            if grid[[row - 1, col - 1]] == id {
-                return [p, Coord { x: col, y: row - 1 }];
-            }
-            // moving down
-            return [p, Coord { x: col, y: row + 1 }];
+/// The shape of one region's boundary, from [`analyze_region`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionTopology {
+    /// how many rings the boundary assembles into
+    pub rings: usize,
+    /// how many of those are outer boundaries, one for each separate piece of the region
+    pub outer: usize,
+    /// how many are holes
+    pub holes: usize,
+    /// the vertices where four boundary edges meet, where the region touches itself (or
+    /// another part of itself) at a corner, sorted by `(y, x)`
+    pub knots: Vec<Coord<usize>>,
+    /// whether the region is one piece with no holes
+    pub simply_connected: bool,
+}
 
-        // moving left
-        } else if p.x == c.x + 1 {
-            // moving up
-            if grid[[row - 1, col]] == id {
-                return [p, Coord { x: col, y: row - 1 }];
-            }
-            // moving down
-            return [p, Coord { x: col, y: row + 1 }];
+/// Works out how a region's boundary fits together
+///
+/// A region whose boundary comes out as several rings may be separate blobs, have holes,
+/// or touch itself at a corner, a knot, where [`edges_to_multilinestring`] splits the
+/// boundary into simple rings.  This assembles the rings the same way and says which, so a
+/// basin that pinches down to a single point can be flagged.
+///
+/// # Parameters
+///
+/// - `id`, `edges`, `grid` - as for [`edges_to_multilinestring`]
+///
+/// # Panics
+///
+/// If the edges don't make rings, as for [`edges_to_multilinestring`].
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// // two blobs touching at a corner
+/// let grid = array![
+///     [1, 1, 0],
+///     [1, 1, 0],
+///     [0, 0, 1],
+/// ];
+/// let e = geospatial::marching_squares(&grid);
+/// let t = geospatial::analyze_region(1, &e[&1], &grid);
+/// assert_eq!((t.rings, t.outer, t.holes), (2, 2, 0));
+/// assert_eq!(t.knots, vec![Coord { x: 2, y: 2 }]);
+/// assert!(!t.simply_connected);
+///
+/// let t = geospatial::analyze_region(0, &e[&0], &grid);
+/// assert_eq!((t.rings, t.outer), (2, 2));
+///
+/// let grid = array![[1, 1], [1, 0]];
+/// let e = geospatial::marching_squares(&grid);
+/// assert!(geospatial::analyze_region(1, &e[&1], &grid).simply_connected);
+/// ```
+pub fn analyze_region<T>(id: T, edges: &[GridEdge], grid: &Array2<T>) -> RegionTopology
+where
+    T: Eq + Hash + Copy,
+{
+    let mut degree: LabelMap<Coord<usize>, usize> = LabelMap::default();
+    for (a, b) in edges {
+        *degree.entry(*a).or_default() += 1;
+        *degree.entry(*b).or_default() += 1;
+    }
+    let mut knots: Vec<Coord<usize>> = degree
+        .into_iter()
+        .filter_map(|(p, n)| (n == 4).then_some(p))
+        .collect();
+    knots.sort_by_key(|p| (p.y, p.x));
 
-        // moving down
-        } else if p.y == c.y - 1 {
-            // moving left
-            if grid[[row - 1, col - 1]] == id {
-                return [p, Coord { x: col - 1, y: row }];
-            }
-            return [p, Coord { x: col + 1, y: row }];
+    let mls = edges_to_multilinestring(id, edges, grid);
+    let outer = mls
+        .0
+        .iter()
+        .filter(|ring| ring_is_outer(ring, id, grid))
+        .count();
+    let holes = mls.0.len() - outer;
+    RegionTopology {
+        rings: mls.0.len(),
+        outer,
+        holes,
+        knots,
+        simply_connected: outer == 1 && holes == 0,
+    }
+}
 
-        // moving up
-        } else {
-            // moving left
-            if grid[[row, col - 1]] == id {
-                return [p, Coord { x: col - 1, y: row }];
-            }
-            // moving right
-            return [p, Coord { x: col + 1, y: row }];
+/// Marching squares with every edge oriented so its region is on the left
+///
+/// The same edges, in the same order, as [`marching_squares`], but each edge points the
+/// way that puts the region it is listed under on its left.  Left is in the shoelace
+/// sense, as if `y` increased upwards, so following the edges goes counter-clockwise (by
+/// signed area) around the outside of a region and clockwise around its holes.  Drawn with
+/// rows increasing down the page that looks like the reverse.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// // (0,0) --> (1,0)
+/// //   ^         |
+/// //   |    1    |
+/// //   |         v
+/// // (0,1) <-- (1,1)
+/// let grid = array![[1]];
+/// assert_eq!(geospatial::marching_squares_oriented(&grid)[&1],
+///     vec![
+///         (Coord{ x: 0, y: 0}, Coord{ x: 1, y: 0}),
+///         (Coord{ x: 1, y: 1}, Coord{ x: 0, y: 1}),
+///         (Coord{ x: 0, y: 1}, Coord{ x: 0, y: 0}),
+///         (Coord{ x: 1, y: 0}, Coord{ x: 1, y: 1}),
+///     ]
+/// );
+///
+/// // the edge between two regions appears once each way
+/// let grid = array![[1, 2]];
+/// let e = geospatial::marching_squares_oriented(&grid);
+/// assert!(e[&1].contains(&(Coord{ x: 1, y: 0}, Coord{ x: 1, y: 1})));
+/// assert!(e[&2].contains(&(Coord{ x: 1, y: 1}, Coord{ x: 1, y: 0})));
+/// ```
+pub fn marching_squares_oriented<'a, T>(
+    grid: impl Into<ArrayView2<'a, T>>,
+) -> LabelMap<T, Vec<GridEdge>>
+where
+    T: Eq + Hash + Clone + 'a,
+{
+    MarchingSquares::new().oriented(true).run(grid)
+}
+
+/// Every boundary edge once, with the label on each side
+///
+/// [`marching_squares`] lists an edge between two regions under both of them.  This is the
+/// half-edge view instead, each edge appearing once as `(from, to, left, right)`.
+/// Horizontal edges always point in `+x` and vertical edges in `+y`, and left and right are
+/// in the shoelace sense, like [`marching_squares_oriented`], so for a horizontal edge the
+/// `left` label is the cell below (the greater row) and for a vertical edge it is the cell
+/// to the left.  Edges on the grid border have `outside` as the label beyond the grid.
+///
+/// Edges are in scan order: the horizontal edges along the top of a row of cells, then the
+/// vertical edges through it, a row at a time.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// let grid = array![[1, 2]];
+/// assert_eq!(
+///     geospatial::marching_squares_halfedges(&grid, 0),
+///     vec![
+///         (Coord { x: 0, y: 0 }, Coord { x: 1, y: 0 }, 1, 0),
+///         (Coord { x: 1, y: 0 }, Coord { x: 2, y: 0 }, 2, 0),
+///         (Coord { x: 0, y: 0 }, Coord { x: 0, y: 1 }, 0, 1),
+///         (Coord { x: 1, y: 0 }, Coord { x: 1, y: 1 }, 1, 2),
+///         (Coord { x: 2, y: 0 }, Coord { x: 2, y: 1 }, 2, 0),
+///         (Coord { x: 0, y: 1 }, Coord { x: 1, y: 1 }, 0, 1),
+///         (Coord { x: 1, y: 1 }, Coord { x: 2, y: 1 }, 0, 2),
+///     ]
+/// );
+/// ```
+pub fn marching_squares_halfedges<T>(
+    grid: &Array2<T>,
+    outside: T,
+) -> Vec<(Coord<usize>, Coord<usize>, T, T)>
+where
+    T: Eq + Copy,
+{
+    halfedges_iter(grid)
+        .map(|(a, b, left, right)| (a, b, left.unwrap_or(outside), right.unwrap_or(outside)))
+        .collect()
+}
+
+// lazily scans the grid for every boundary edge once, as (from, to, left, right) with None
+// for beyond the grid.  see marching_squares_halfedges for the order and orientation
+fn halfedges_iter<T>(
+    grid: &Array2<T>,
+) -> impl Iterator<Item = (Coord<usize>, Coord<usize>, Option<T>, Option<T>)> + '_
+where
+    T: Eq + Copy,
+{
+    let (nrows, ncols) = grid.dim();
+    // the cell at [r - 1, c - 1], offset to avoid going negative
+    let at = move |r: usize, c: usize| {
+        (r >= 1 && c >= 1 && r <= nrows && c <= ncols).then(|| grid[[r - 1, c - 1]])
+    };
+    let boundary = |left: Option<T>, right: Option<T>| left.is_none() || left != right;
+
+    (0..=nrows).flat_map(move |r| {
+        let horizontal = (0..ncols).filter_map(move |c| {
+            let (left, right) = (at(r + 1, c + 1), at(r, c + 1));
+            boundary(left, right).then_some((
+                Coord { x: c, y: r },
+                Coord { x: c + 1, y: r },
+                left,
+                right,
+            ))
+        });
+        let vertical = (0..=ncols).filter(move |_| r < nrows).filter_map(move |c| {
+            let (left, right) = (at(r + 1, c), at(r + 1, c + 1));
+            boundary(left, right).then_some((
+                Coord { x: c, y: r },
+                Coord { x: c, y: r + 1 },
+                left,
+                right,
+            ))
+        });
+        horizontal.chain(vertical)
+    })
+}
+
+/// Streams the edges of [`marching_squares`] without building the `HashMap`
+///
+/// Yields `(label, edge)` pairs lazily as the grid is scanned, so a single label can be
+/// picked out of a grid too big to hold every edge.  The edges are the same as
+/// [`marching_squares`]: an edge between two regions is yielded once for each of them.
+///
+/// The order is the row major scan of [`marching_squares_halfedges`], each edge giving the
+/// label on its left and then the one on its right.  This is deterministic, but not the
+/// order [`marching_squares`] puts the edges in.
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use ndarray::array;
+///
+/// let grid = array![[1, 2]];
+/// let ones: Vec<_> = geospatial::marching_squares_edges_iter(&grid)
+///     .filter(|(label, _)| *label == 1)
+///     .map(|(_, e)| e)
+///     .collect();
+/// assert_eq!(ones, vec![
+///     (Coord { x: 0, y: 0 }, Coord { x: 1, y: 0 }),
+///     (Coord { x: 0, y: 0 }, Coord { x: 0, y: 1 }),
+///     (Coord { x: 1, y: 0 }, Coord { x: 1, y: 1 }),
+///     (Coord { x: 0, y: 1 }, Coord { x: 1, y: 1 }),
+/// ]);
+/// assert_eq!(geospatial::marching_squares_edges_iter(&grid).count(), 8);
+/// ```
+pub fn marching_squares_edges_iter<T>(grid: &Array2<T>) -> impl Iterator<Item = (T, GridEdge)> + '_
+where
+    T: Eq + Copy,
+{
+    halfedges_iter(grid).flat_map(|(a, b, left, right)| {
+        [left, right]
+            .into_iter()
+            .flatten()
+            .map(move |label| (label, (a, b)))
+    })
+}
+
+/// Options for [`marching_squares`], for when the plain function isn't enough
+///
+/// Start from [`MarchingSquares::new`], switch on what you need, then [`run`] it on a grid.
+/// With no options set this gives exactly [`marching_squares`], which is just a wrapper
+/// around it.
+///
+/// [`run`]: MarchingSquares::run
+///
+/// # Examples
+///
+/// ```
+/// use geo::Coord;
+/// use geospatial::MarchingSquares;
+/// use ndarray::array;
+///
+/// let grid = array![
+///     [-1, 1],
+///     [ 1, 1],
+/// ];
+/// let e = MarchingSquares::new().nodata(-1).oriented(true).run(&grid);
+/// assert!(!e.contains_key(&-1));
+/// assert_eq!(e[&1].len(), 8);
+/// // the edge against the nodata cell is still part of 1's boundary, with 1 on its left
+/// assert!(e[&1].contains(&(Coord { x: 1, y: 1 }, Coord { x: 1, y: 0 })));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarchingSquares<T> {
+    nodata: Option<T>,
+    oriented: bool,
+    connectivity: Connectivity,
+}
+
+impl<T> Default for MarchingSquares<T> {
+    fn default() -> Self {
+        MarchingSquares {
+            nodata: None,
+            oriented: false,
+            connectivity: Connectivity::Four,
         }
     }
+}
 
-    // a helper that makes a single ring.  assumes we start at a point with two neighbours
-    // id and grid are used to figure out correct direction at a knot
-    fn aring<T>(
-        adj: &HashMap<Coord<usize>, Vec<Coord<usize>>>,
-        start: Coord<usize>,
-        id: T,
-        grid: &Array2<T>,
-    ) -> Vec<Coord<usize>>
+impl<T> MarchingSquares<T>
+where
+    T: Eq + Hash + Clone,
+{
+    /// The default options, giving the same edges as [`marching_squares`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treat cells with this value as outside the grid
+    ///
+    /// They get no edges of their own, so there is no `nodata` key, but the regions next to
+    /// them are still closed off by an edge against them, just as at the grid border.
+    pub fn nodata(mut self, nodata: T) -> Self {
+        self.nodata = Some(nodata);
+        self
+    }
+
+    /// Point every edge so the region it is listed under is on its left, as
+    /// [`marching_squares_oriented`] does
+    pub fn oriented(mut self, oriented: bool) -> Self {
+        self.oriented = oriented;
+        self
+    }
+
+    /// Join regions through corners they touch diagonally, as
+    /// [`marching_squares_connectivity`] does with [`Connectivity::Eight`]
+    pub fn connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
+
+    /// Extracts the boundary edges of every region in `grid`
+    pub fn run<'a>(&self, grid: impl Into<ArrayView2<'a, T>>) -> LabelMap<T, Vec<GridEdge>>
     where
-        T: Eq + Hash + Copy,
+        T: 'a,
     {
-        let mut ring: Vec<Coord<usize>> = Vec::new();
-        let mut cur = start;
-        let mut prev: Coord<usize> = adj[&cur][0];
+        let view: ArrayView2<T> = grid.into();
+        let grid = &view;
+        let keep = |label: &T| self.nodata.as_ref() != Some(label);
 
-        // storage for knot-case neighbours
-        let mut knot_coords: [Coord<usize>; 2];
+        // count first so every vec is allocated once at its final size, big regions
+        // otherwise reallocate their way up through the doublings.  labels are only cloned
+        // the first time they're seen
+        let mut counts: LabelMap<T, usize> = LabelMap::default();
+        marching_squares_scan(grid, |label, _| {
+            if let Some(n) = counts.get_mut(label) {
+                *n += 1;
+            } else if keep(label) {
+                counts.insert(label.clone(), 1);
+            }
+        });
 
-        loop {
-            ring.push(cur);
-            let mut n: &[Coord<usize>] = &adj[&cur];
-            if n.len() == 4 {
-                knot_coords = adjcoords(prev, cur, id, grid);
-                n = &knot_coords;
-            }
-            if prev == n[0] && n[1] != start {
-                prev = cur;
-                cur = n[1];
-            } else if prev == n[1] && n[0] != start {
-                prev = cur;
-                cur = n[0];
-            } else {
-                break;
+        let mut ret: LabelMap<T, Vec<GridEdge>> = LabelMap::default();
+        ret.reserve(counts.len());
+        for (label, n) in counts {
+            ret.insert(label, Vec::with_capacity(n));
+        }
+        marching_squares_scan(grid, |label, edge| {
+            if let Some(v) = ret.get_mut(label) {
+                v.push(edge);
+            }
+        });
+        let diagonals = match self.connectivity {
+            Connectivity::Four => Vec::new(),
+            Connectivity::Eight => cut_knots(grid, &mut ret, keep),
+        };
+
+        if self.oriented {
+            for (id, edges) in ret.iter_mut() {
+                for e in edges.iter_mut() {
+                    if !cell_left_of(e.0, e.1, grid).is_some_and(|ix| grid[ix] == *id) {
+                        *e = (e.1, e.0);
+                    }
+                }
             }
         }
-        ring.push(start);
 
-        return ring;
+        for (label, edge, on_left) in diagonals {
+            if let Some(v) = ret.get_mut(label) {
+                v.push(if self.oriented && !on_left {
+                    (edge.1, edge.0)
+                } else {
+                    edge
+                });
+            }
+        }
+        ret
     }
+}
 
-    // start with a copy of edges since we will be constantly updating these
-    let mut edges = edges.clone();
+// joins diagonal neighbours for eight connectivity.  at every knot where one diagonal pair
+// of cells has the same label, the two unit edges of each of the other cells that meet
+// at the knot are swapped for a diagonal across that cell, so the pair is joined by a
+// band taking the half of each other cell nearest the knot.  unit edges are taken out of
+// ret, and the diagonals returned as (label, edge going left to right, label on its left)
+fn cut_knots<'g, T, F>(
+    grid: &'g ArrayView2<T>,
+    ret: &mut LabelMap<T, Vec<GridEdge>>,
+    keep: F,
+) -> Vec<(&'g T, GridEdge, bool)>
+where
+    T: Eq + Hash,
+    F: Fn(&T) -> bool,
+{
+    let (nrows, ncols) = grid.dim();
+    // what each cell has been so far, 1 a winner at some knot, 2 cut in half
+    let mut role: Array2<u8> = Array2::zeros((nrows, ncols));
+    let mut drop: LabelMap<&T, LabelSet<GridEdge>> = LabelMap::default();
+    let mut diagonals = Vec::new();
+    let at = |x: usize, y: usize| Coord { x, y };
 
-    let mut rings: Vec<LineString<usize>> = Vec::new();
-    while edges.len() != 0 {
-        // build the adjancey
-        let mut adj: HashMap<Coord<usize>, Vec<Coord<usize>>> = HashMap::new();
-        for (a, b) in &edges {
-            adj.entry(*a).or_default().push(*b);
-            adj.entry(*b).or_default().push(*a);
-        }
-        assert!(adj.values().all(|p| p.len() == 2 || p.len() == 4));
+    for y in 1..nrows {
+        for x in 1..ncols {
+            let (tl, tr) = (&grid[[y - 1, x - 1]], &grid[[y - 1, x]]);
+            let (bl, br) = (&grid[[y, x - 1]], &grid[[y, x]]);
+            if tl == tr || tr == br || br == bl || bl == tl {
+                continue;
+            }
+            let k = at(x, y);
+            let up = (at(x, y - 1), k);
+            let right = (k, at(x + 1, y));
+            let down = (k, at(x, y + 1));
+            let left = (at(x - 1, y), k);
 
-        // first point of first edge will do to make a ring
-        let start = edges[0].0;
-        let ring = aring::<T>(&adj, start, id, &grid);
-        rings.push(LineString(ring.clone()));
+            // ties go to the top left to bottom right pair
+            let (winner, winners, losers) = if tl == br && keep(tl) {
+                let losers = [
+                    ([y - 1, x], [up, right], (at(x, y - 1), at(x + 1, y)), true),
+                    (
+                        [y, x - 1],
+                        [down, left],
+                        (at(x - 1, y), at(x, y + 1)),
+                        false,
+                    ),
+                ];
+                (tl, [[y - 1, x - 1], [y, x]], losers)
+            } else if tr == bl && keep(tr) {
+                let losers = [
+                    (
+                        [y - 1, x - 1],
+                        [up, left],
+                        (at(x - 1, y), at(x, y - 1)),
+                        true,
+                    ),
+                    ([y, x], [right, down], (at(x, y + 1), at(x + 1, y)), false),
+                ];
+                (tr, [[y - 1, x], [y, x - 1]], losers)
+            } else {
+                continue;
+            };
+            // a cell is cut at most once, and only if it hasn't already joined a pair at
+            // another knot, otherwise one join would undo the other.  the first knot to
+            // get a cell wins and the rest are left as they are
+            if losers.iter().any(|(ix, ..)| role[*ix] != 0)
+                || winners.iter().any(|ix| role[*ix] == 2)
+            {
+                continue;
+            }
+            for ix in winners {
+                role[ix] = 1;
+            }
 
-        let myedges: HashSet<(Coord<usize>, Coord<usize>)> = ring
-            .windows(2)
-            .flat_map(|w| vec![(w[0], w[1]), (w[1], w[0])])
-            .collect();
-        edges = edges.into_iter().filter(|e| !myedges.contains(e)).collect();
+            drop.entry(winner)
+                .or_default()
+                .extend([up, right, down, left]);
+            for (ix, sides, diagonal, winner_left) in losers {
+                role[ix] = 2;
+                drop.entry(&grid[ix]).or_default().extend(sides);
+                diagonals.push((winner, diagonal, winner_left));
+                diagonals.push((&grid[ix], diagonal, !winner_left));
+            }
+        }
     }
 
-    return MultiLineString::new(rings);
+    for (label, gone) in drop {
+        if let Some(v) = ret.get_mut(label) {
+            v.retain(|e| !gone.contains(e));
+        }
+    }
+    diagonals
 }